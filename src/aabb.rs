@@ -1,7 +1,6 @@
 use std::mem;
 
 use crate::ray;
-use crate::utility;
 use crate::vec;
 
 #[derive(Clone, Copy)]
@@ -33,13 +32,13 @@ impl Aabb {
     pub fn hit(&self, ray: &ray::Ray, tmin: &mut f32, tmax: &mut f32) -> bool {
         for i in 0..3 {
             let inv_d = 1.0 / ray.direction()[i];
-            let mut t0 = self.min()[i] - ray.origin()[i] * inv_d;
-            let mut t1 = self.max()[i] - ray.origin()[i] * inv_d;
+            let mut t0 = (self.min()[i] - ray.origin()[i]) * inv_d;
+            let mut t1 = (self.max()[i] - ray.origin()[i]) * inv_d;
             if inv_d < 0.0 {
                 mem::swap(&mut t0, &mut t1);
             }
             *tmin = if t0 > *tmin { t0 } else { *tmin };
-            *tmin = if t1 < *tmax { t1 } else { *tmax };
+            *tmax = if t1 < *tmax { t1 } else { *tmax };
             if tmax <= tmin {
                 return false;
             }
@@ -61,3 +60,54 @@ pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
     );
     Aabb::new(small, big)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(
+            vec::Point3::new(1.0, 1.0, 1.0),
+            vec::Point3::new(2.0, 2.0, 2.0),
+        )
+    }
+
+    fn hit(ray: &ray::Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let (mut t_min, mut t_max) = (t_min, t_max);
+        if unit_box().hit(ray, &mut t_min, &mut t_max) {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn slabs_clip_the_ray() {
+        // Away from the origin, so that the distances depend on dividing the whole offset
+        let ray = ray::Ray::new(
+            vec::Point3::new(-2.0, 1.5, 1.5),
+            vec::Vec3::new(2.0, 0.0, 0.0),
+            0.0,
+        );
+        let (t0, t1) = hit(&ray, 0.0, 100.0).unwrap();
+        assert!((t0 - 1.5).abs() < 1e-6 && (t1 - 2.0).abs() < 1e-6);
+
+        // Going the other way
+        let ray = ray::Ray::new(
+            vec::Point3::new(4.0, 1.5, 1.5),
+            vec::Vec3::new(-1.0, 0.0, 0.0),
+            0.0,
+        );
+        let (t0, t1) = hit(&ray, 0.0, 100.0).unwrap();
+        assert!((t0 - 2.0).abs() < 1e-6 && (t1 - 3.0).abs() < 1e-6);
+
+        // Ending before the box, or passing beside it
+        assert!(hit(&ray, 0.0, 1.5).is_none());
+        let ray = ray::Ray::new(
+            vec::Point3::new(-2.0, 2.5, 1.5),
+            vec::Vec3::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(hit(&ray, 0.0, 100.0).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{aabb, hittable, material, ray, vec};
+use std::rc::Rc;
+
+// Padding keeps the bounding boxes of flat rectangles from having zero width
+const THICKNESS: f32 = 0.0001;
+
+pub struct XyRect {
+    x0: f32,
+    x1: f32,
+    y0: f32,
+    y1: f32,
+    k: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl XyRect {
+    pub fn new(
+        x0: f32,
+        x1: f32,
+        y0: f32,
+        y1: f32,
+        k: f32,
+        material: Rc<dyn material::Material>,
+    ) -> XyRect {
+        XyRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for XyRect {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let t = (self.k - ray.origin().z()) / ray.direction().z();
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        let x = ray.origin().x() + t * ray.direction().x();
+        let y = ray.origin().y() + t * ray.direction().y();
+        if !(self.x0..=self.x1).contains(&x) || !(self.y0..=self.y1).contains(&y) {
+            return false;
+        }
+
+        record.u = (x - self.x0) / (self.x1 - self.x0);
+        record.v = (y - self.y0) / (self.y1 - self.y0);
+        record.t = t;
+        record.p = ray.at(t);
+        record.set_normale_face(ray, &vec::Vec3::new(0.0, 0.0, 1.0));
+        record.tangent = vec::Vec3::new(1.0, 0.0, 0.0);
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = aabb::Aabb::new(
+            vec::Point3::new(self.x0, self.y0, self.k - THICKNESS),
+            vec::Point3::new(self.x1, self.y1, self.k + THICKNESS),
+        );
+        true
+    }
+}
+
+pub struct XzRect {
+    x0: f32,
+    x1: f32,
+    z0: f32,
+    z1: f32,
+    k: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl XzRect {
+    pub fn new(
+        x0: f32,
+        x1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: Rc<dyn material::Material>,
+    ) -> XzRect {
+        XzRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for XzRect {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let t = (self.k - ray.origin().y()) / ray.direction().y();
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        let x = ray.origin().x() + t * ray.direction().x();
+        let z = ray.origin().z() + t * ray.direction().z();
+        if !(self.x0..=self.x1).contains(&x) || !(self.z0..=self.z1).contains(&z) {
+            return false;
+        }
+
+        record.u = (x - self.x0) / (self.x1 - self.x0);
+        record.v = (z - self.z0) / (self.z1 - self.z0);
+        record.t = t;
+        record.p = ray.at(t);
+        record.set_normale_face(ray, &vec::Vec3::new(0.0, 1.0, 0.0));
+        record.tangent = vec::Vec3::new(1.0, 0.0, 0.0);
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = aabb::Aabb::new(
+            vec::Point3::new(self.x0, self.k - THICKNESS, self.z0),
+            vec::Point3::new(self.x1, self.k + THICKNESS, self.z1),
+        );
+        true
+    }
+}
+
+pub struct YzRect {
+    y0: f32,
+    y1: f32,
+    z0: f32,
+    z1: f32,
+    k: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl YzRect {
+    pub fn new(
+        y0: f32,
+        y1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        material: Rc<dyn material::Material>,
+    ) -> YzRect {
+        YzRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for YzRect {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let t = (self.k - ray.origin().x()) / ray.direction().x();
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        let y = ray.origin().y() + t * ray.direction().y();
+        let z = ray.origin().z() + t * ray.direction().z();
+        if !(self.y0..=self.y1).contains(&y) || !(self.z0..=self.z1).contains(&z) {
+            return false;
+        }
+
+        record.u = (y - self.y0) / (self.y1 - self.y0);
+        record.v = (z - self.z0) / (self.z1 - self.z0);
+        record.t = t;
+        record.p = ray.at(t);
+        record.set_normale_face(ray, &vec::Vec3::new(1.0, 0.0, 0.0));
+        record.tangent = vec::Vec3::new(0.0, 1.0, 0.0);
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = aabb::Aabb::new(
+            vec::Point3::new(self.k - THICKNESS, self.y0, self.z0),
+            vec::Point3::new(self.k + THICKNESS, self.y1, self.z1),
+        );
+        true
+    }
+}
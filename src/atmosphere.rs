@@ -0,0 +1,99 @@
+use crate::utility::*;
+use crate::vec::Color;
+use rand::prelude::*;
+
+// Homogeneous medium filling the whole scene, the camera and every object are inside of it
+pub struct Atmosphere {
+    scattering: Color,
+    extinction: Color,
+}
+
+// Outcome of tracking a ray through the atmosphere up to the next surface
+pub struct AtmosphereSample {
+    // Distance along the ray at which it scatters, if it does before reaching the surface
+    pub scatter_distance: Option<f32>,
+    // Throughput weight of the sampled event
+    pub weight: Color,
+}
+
+impl Atmosphere {
+    // Coefficients are per unit of distance for each color channel
+    pub fn new(absorption: Color, scattering: Color) -> Atmosphere {
+        Atmosphere {
+            scattering,
+            extinction: absorption + scattering,
+        }
+    }
+
+    pub fn transmittance(&self, distance: f32) -> Color {
+        // A channel without extinction lets everything through, even over an infinite distance
+        let channel = |sigma_t: f32| {
+            if sigma_t == 0.0 {
+                1.0
+            } else {
+                (-sigma_t * distance).exp()
+            }
+        };
+        Color::new(
+            channel(self.extinction.x()),
+            channel(self.extinction.y()),
+            channel(self.extinction.z()),
+        )
+    }
+
+    // Samples a free-flight distance for a ray segment of length `distance`, which is infinite
+    // when the ray doesn't hit anything. The distance is sampled with the extinction of a random
+    // channel and weighted by the pdf averaged over all channels.
+    pub fn sample(&self, distance: f32, rng: &mut ThreadRng) -> AtmosphereSample {
+        let channel = ((random_double(rng) * 3.0) as usize).min(2);
+        let sigma_t = self.extinction[channel];
+        let flight = if sigma_t > 0.0 {
+            -(1.0 - random_double(rng)).ln() / sigma_t
+        } else {
+            INFINITY
+        };
+
+        if flight < distance {
+            let transmittance = self.transmittance(flight);
+            let density = self.extinction * transmittance;
+            let pdf = (density.x() + density.y() + density.z()) / 3.0;
+            return AtmosphereSample {
+                scatter_distance: Some(flight),
+                weight: self.scattering * transmittance / pdf,
+            };
+        }
+
+        let transmittance = self.transmittance(distance);
+        let probability = (transmittance.x() + transmittance.y() + transmittance.z()) / 3.0;
+        AtmosphereSample {
+            scatter_distance: None,
+            weight: if probability > 0.0 {
+                transmittance / probability
+            } else {
+                Color::empty()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_channel_at_infinite_distance() {
+        let atmosphere = Atmosphere::new(Color::empty(), Color::new(0.0, 0.1, 0.2));
+        let transmittance = atmosphere.transmittance(INFINITY);
+        assert_eq!(transmittance.x(), 1.0);
+        assert_eq!(transmittance.y(), 0.0);
+        assert_eq!(transmittance.z(), 0.0);
+
+        // Rays escaping the scene keep a finite weight
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let sample = atmosphere.sample(INFINITY, &mut rng);
+            let weight = sample.weight;
+            assert!(weight.x().is_finite() && weight.y().is_finite() && weight.z().is_finite());
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{aabb, aarect, hittable, instance, material, ray, vec};
+use std::rc::Rc;
+
+pub struct BoxShape {
+    box_min: vec::Point3,
+    box_max: vec::Point3,
+    sides: hittable::HittableList,
+}
+
+impl BoxShape {
+    pub fn new(p0: vec::Point3, p1: vec::Point3, material: Rc<dyn material::Material>) -> BoxShape {
+        let mut sides = hittable::HittableList::new();
+
+        // The rectangles face along their axis, the ones at the low end are turned to face out
+        sides.add(Box::new(aarect::XyRect::new(
+            p0.x(),
+            p1.x(),
+            p0.y(),
+            p1.y(),
+            p1.z(),
+            material.clone(),
+        )));
+        sides.add(Box::new(instance::FlipFace::new(Box::new(
+            aarect::XyRect::new(p0.x(), p1.x(), p0.y(), p1.y(), p0.z(), material.clone()),
+        ))));
+
+        sides.add(Box::new(aarect::XzRect::new(
+            p0.x(),
+            p1.x(),
+            p0.z(),
+            p1.z(),
+            p1.y(),
+            material.clone(),
+        )));
+        sides.add(Box::new(instance::FlipFace::new(Box::new(
+            aarect::XzRect::new(p0.x(), p1.x(), p0.z(), p1.z(), p0.y(), material.clone()),
+        ))));
+
+        sides.add(Box::new(aarect::YzRect::new(
+            p0.y(),
+            p1.y(),
+            p0.z(),
+            p1.z(),
+            p1.x(),
+            material.clone(),
+        )));
+        sides.add(Box::new(instance::FlipFace::new(Box::new(
+            aarect::YzRect::new(p0.y(), p1.y(), p0.z(), p1.z(), p0.x(), material),
+        ))));
+
+        BoxShape {
+            box_min: p0,
+            box_max: p1,
+            sides,
+        }
+    }
+}
+
+impl hittable::Hittable for BoxShape {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        self.sides.hit(ray, t_min, t_max, record)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = aabb::Aabb::new(self.box_min, self.box_max);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::utility::INFINITY;
+    use crate::vec::{Color, Point3, Vec3};
+
+    #[test]
+    fn faces_point_outward() {
+        let unit_box = BoxShape::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Rc::new(material::Lambertian::new(Color::empty())),
+        );
+        let outward = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        // Slightly off the center so rays don't go through an edge
+        let inside = Point3::new(0.1, 0.2, 0.3);
+        for n in outward.iter() {
+            let origin = inside + *n * (5.0 - vec::dot(n, &inside));
+            let ray = ray::Ray::new(origin, -*n, 0.0);
+            let mut record = hittable::HitRecord::empty();
+            let (mut t_min, mut t_max) = (0.001, INFINITY);
+            assert!(unit_box.hit(&ray, &mut t_min, &mut t_max, &mut record));
+            assert!((record.t - 4.0).abs() < 1.0e-4);
+            assert!(record.front_face);
+            assert!((vec::dot(&record.normal, n) - 1.0).abs() < 1.0e-6);
+
+            // From the inside the same face is hit from the back
+            let ray = ray::Ray::new(inside, *n, 0.0);
+            let (mut t_min, mut t_max) = (0.001, INFINITY);
+            assert!(unit_box.hit(&ray, &mut t_min, &mut t_max, &mut record));
+            assert!(!record.front_face);
+            assert!((vec::dot(&record.normal, n) + 1.0).abs() < 1.0e-6);
+        }
+    }
+}
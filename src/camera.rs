@@ -10,8 +10,10 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f32,
+    // Shutter open and close times
+    time0: f32,
+    time1: f32,
 }
 
 impl Camera {
@@ -47,11 +49,23 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
             lens_radius,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
+    // Keeps the shutter open from `time0` to `time1`, each ray gets a random time in between
+    pub fn with_shutter(mut self, time0: f32, time1: f32) -> Camera {
+        self.time0 = time0;
+        self.time1 = time1;
+        self
+    }
+
+    pub fn shutter(&self) -> (f32, f32) {
+        (self.time0, self.time1)
+    }
+
     pub fn get_ray(&self, s: f32, t: f32, rng: &mut ThreadRng) -> Ray {
         let rd = random_in_unit_disc(rng) * self.lens_radius;
         let offset = self.u * rd.x() + self.v * rd.y();
@@ -59,6 +73,7 @@ impl Camera {
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset,
+            random_double_range(rng, self.time0, self.time1),
         )
     }
 }
@@ -1,7 +1,12 @@
-use crate::clamp;
+use crate::utility::clamp;
 use crate::vec::Color;
 use std::io::Write;
 
+// Relative luminance of a linear sRGB color
+pub fn luminance(color: &Color) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
 pub fn write_color<R: Write>(out: &mut R, pixel_color: Color, samples_per_pixel: i32) {
     let (mut r, mut g, mut b) = (pixel_color.x(), pixel_color.y(), pixel_color.z());
 
@@ -25,5 +30,5 @@ pub fn write_color<R: Write>(out: &mut R, pixel_color: Color, samples_per_pixel:
         (256.0 * clamp(g, 0.0, 0.999)) as i32,
         (256.0 * clamp(b, 0.0, 0.999)) as i32,
     );
-    out.write(color_line.as_bytes()).unwrap();
+    out.write_all(color_line.as_bytes()).unwrap();
 }
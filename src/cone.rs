@@ -0,0 +1,105 @@
+use crate::{aabb, disk, hittable, material, onb, ray, utility::*, vec::*};
+use std::rc::Rc;
+
+// Cone with a base of `radius` at `base` narrowing to its apex at `base + axis`, optionally
+// closed with a disk at the base
+pub struct Cone {
+    base: Point3,
+    axes: onb::Onb,
+    radius: f32,
+    height: f32,
+    capped: bool,
+    material: Rc<dyn material::Material>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point3,
+        axis: Vec3,
+        radius: f32,
+        capped: bool,
+        material: Rc<dyn material::Material>,
+    ) -> Cone {
+        Cone {
+            base,
+            axes: onb::Onb::build_from_w(&axis),
+            radius,
+            height: axis.length(),
+            capped,
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for Cone {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        // Intersect in the local frame where the axis is +z and the base is at the origin.
+        // The surface is x^2 + y^2 = k^2 (h - z)^2 with k = r / h.
+        let o = self.axes.to_local(&(ray.origin() - self.base));
+        let d = self.axes.to_local(&ray.direction());
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.z();
+
+        let mut closest = *t_max;
+        let mut local_normal = Vec3::empty();
+        let mut uv = (0.0, 0.0);
+
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let half_b = o.x() * d.x() + o.y() * d.y() + k2 * h * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * h * h;
+        let discriminant = half_b * half_b - a * c;
+        if a.abs() > f32::EPSILON && discriminant > 0.0 {
+            let root = discriminant.sqrt();
+            for t in [(-half_b - root) / a, (-half_b + root) / a].iter() {
+                let z = o.z() + t * d.z();
+                if *t > *t_min && *t < closest && (0.0..=self.height).contains(&z) {
+                    let x = o.x() + t * d.x();
+                    let y = o.y() + t * d.y();
+                    closest = *t;
+                    local_normal = unit_vector(Vec3::new(x, y, k2 * (self.height - z)));
+                    uv = ((y.atan2(x) + PI) / (2.0 * PI), z / self.height);
+                }
+            }
+        }
+
+        if self.capped && d.z().abs() > f32::EPSILON {
+            let t = -o.z() / d.z();
+            let x = o.x() + t * d.x();
+            let y = o.y() + t * d.y();
+            let r2 = x * x + y * y;
+            if t > *t_min && t < closest && r2 <= self.radius * self.radius {
+                closest = t;
+                local_normal = Vec3::new(0.0, 0.0, -1.0);
+                uv = ((y.atan2(x) + PI) / (2.0 * PI), r2.sqrt() / self.radius);
+            }
+        }
+
+        if closest >= *t_max {
+            return false;
+        }
+
+        record.t = closest;
+        record.p = ray.at(closest);
+        record.u = uv.0;
+        record.v = uv.1;
+        record.set_normale_face(ray, &self.axes.local(&local_normal));
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let apex = self.base + self.axes.w() * self.height;
+        let half = disk::extent(&self.axes.w(), self.radius);
+        *output_box = aabb::surrounding_box(
+            &aabb::Aabb::new(self.base - half, self.base + half),
+            &aabb::Aabb::new(apex, apex),
+        );
+        true
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{aabb, hittable, material, ray, utility::*, vec};
+use std::rc::Rc;
+
+// Volume of constant density filling a boundary object, which must be closed. Rays scatter
+// inside after an exponentially distributed distance.
+pub struct ConstantMedium {
+    boundary: Box<dyn hittable::Hittable>,
+    phase_function: Rc<dyn material::Material>,
+    neg_inv_density: f32,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn hittable::Hittable>,
+        density: f32,
+        albedo: vec::Color,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            phase_function: Rc::new(material::Isotropic::new(albedo)),
+            neg_inv_density: -1.0 / density,
+        }
+    }
+
+    // Part of the ray between `t_min` and `t_max` that is inside the boundary. The whole line
+    // is searched so that rays starting inside the volume are handled as well.
+    fn inside_interval(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let mut record1 = hittable::HitRecord::empty();
+        let mut record2 = hittable::HitRecord::empty();
+
+        let (mut enter_min, mut enter_max) = (-INFINITY, INFINITY);
+        if !self
+            .boundary
+            .hit(ray, &mut enter_min, &mut enter_max, &mut record1)
+        {
+            return None;
+        }
+        let (mut exit_min, mut exit_max) = (record1.t + 0.0001, INFINITY);
+        if !self
+            .boundary
+            .hit(ray, &mut exit_min, &mut exit_max, &mut record2)
+        {
+            return None;
+        }
+
+        let t_enter = record1.t.max(t_min).max(0.0);
+        let t_exit = record2.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+impl hittable::Hittable for ConstantMedium {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let (t_enter, t_exit) = match self.inside_interval(ray, *t_min, *t_max) {
+            Some(interval) => interval,
+            None => return false,
+        };
+
+        let ray_length = ray.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double(&mut rand::thread_rng()).ln();
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        record.t = t_enter + hit_distance / ray_length;
+        record.p = ray.at(record.t);
+        record.u = 0.0;
+        record.v = 0.0;
+        // Arbitrary, scattering in a volume does not depend on a surface orientation
+        record.normal = vec::Vec3::new(1.0, 0.0, 0.0);
+        record.front_face = true;
+        record.material = self.phase_function.clone();
+        true
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        self.boundary.bounding_box(t0, t1, output_box)
+    }
+
+    // Beer-Lambert law over the length of the ray inside the boundary
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        match self.inside_interval(ray, t_min, t_max) {
+            Some((t_enter, t_exit)) => {
+                ((t_exit - t_enter) * ray.direction().length() / self.neg_inv_density).exp()
+            }
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::box_shape::BoxShape;
+    use crate::hittable::Hittable;
+    use crate::sphere::Sphere;
+    use crate::vec::{Color, Point3, Vec3};
+
+    fn fog_sphere(density: f32) -> ConstantMedium {
+        let boundary = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Rc::new(material::Lambertian::new(Color::empty())),
+        );
+        ConstantMedium::new(Box::new(boundary), density, Color::new(1.0, 1.0, 1.0))
+    }
+
+    fn hit(medium: &ConstantMedium, ray: &ray::Ray) -> Option<f32> {
+        let mut record = hittable::HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if medium.hit(ray, &mut t_min, &mut t_max, &mut record) {
+            Some(record.t)
+        } else {
+            None
+        }
+    }
+
+    fn miss_ratio(medium: &ConstantMedium, ray: &ray::Ray) -> f32 {
+        let trials = 4000;
+        let misses = (0..trials).filter(|_| hit(medium, ray).is_none()).count();
+        misses as f32 / trials as f32
+    }
+
+    #[test]
+    fn dense_medium_scatters_at_boundary() {
+        let medium = fog_sphere(1.0e6);
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let t = hit(&medium, &ray).unwrap();
+        assert!((t - 4.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn thin_medium_is_transparent() {
+        let medium = fog_sphere(1.0e-6);
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(hit(&medium, &ray).is_none());
+    }
+
+    #[test]
+    fn ray_pointing_away_misses() {
+        let medium = fog_sphere(1.0e6);
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(hit(&medium, &ray).is_none());
+    }
+
+    #[test]
+    fn transmittance_through_convex_boundary() {
+        // exp(-density * 2) for the diameter of the unit sphere
+        let medium = fog_sphere(0.5);
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0), 0.0);
+        assert!((miss_ratio(&medium, &ray) - (-1.0f32).exp()).abs() < 0.05);
+
+        let boundary = BoxShape::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Rc::new(material::Lambertian::new(Color::empty())),
+        );
+        let medium = ConstantMedium::new(Box::new(boundary), 0.5, Color::new(1.0, 1.0, 1.0));
+        let ray = ray::Ray::new(Point3::new(0.3, -0.2, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!((miss_ratio(&medium, &ray) - (-1.0f32).exp()).abs() < 0.05);
+    }
+
+    #[test]
+    fn ray_starting_inside() {
+        let medium = fog_sphere(1.0e6);
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let t = hit(&medium, &ray).unwrap();
+        assert!((0.001..0.01).contains(&t));
+
+        // Only the distance from the origin to the boundary is inside the volume
+        let medium = fog_sphere(1.0);
+        let trials = 4000;
+        let mut misses = 0;
+        for _ in 0..trials {
+            match hit(&medium, &ray) {
+                Some(t) => assert!(t <= 1.0),
+                None => misses += 1,
+            }
+        }
+        assert!((misses as f32 / trials as f32 - (-1.0f32).exp()).abs() < 0.05);
+    }
+}
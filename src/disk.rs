@@ -0,0 +1,86 @@
+use crate::{aabb, hittable, material, onb, ray, utility::*, vec, vec::*};
+use std::rc::Rc;
+
+// Minimum extent of the bounding box along the normal of the disk
+const THICKNESS: f32 = 0.0001;
+
+pub struct Disk {
+    center: Point3,
+    radius: f32,
+    axes: onb::Onb,
+    material: Rc<dyn material::Material>,
+}
+
+impl Disk {
+    pub fn new(
+        center: Point3,
+        normal: Vec3,
+        radius: f32,
+        material: Rc<dyn material::Material>,
+    ) -> Disk {
+        Disk {
+            center,
+            radius,
+            axes: onb::Onb::build_from_w(&normal),
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for Disk {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let normal = self.axes.w();
+        let denom = vec::dot(&normal, &ray.direction());
+
+        // If parallel to surface
+        if denom.abs() < f32::EPSILON {
+            return false;
+        }
+
+        let t = vec::dot(&normal, &(self.center - ray.origin())) / denom;
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        let p = ray.at(t);
+        let planar = p - self.center;
+        let distance_squared = planar.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return false;
+        }
+
+        // u goes around the disk, v from the center to the rim
+        let phi = vec::dot(&planar, &self.axes.v()).atan2(vec::dot(&planar, &self.axes.u()));
+        record.u = (phi + PI) / (2.0 * PI);
+        record.v = distance_squared.sqrt() / self.radius;
+        record.t = t;
+        record.p = p;
+        record.set_normale_face(ray, &normal);
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let half = extent(&self.axes.w(), self.radius);
+        let half = Vec3::new(
+            half.x().max(THICKNESS),
+            half.y().max(THICKNESS),
+            half.z().max(THICKNESS),
+        );
+        *output_box = aabb::Aabb::new(self.center - half, self.center + half);
+        true
+    }
+}
+
+// Half extent along each axis of a circle of `radius` perpendicular to the unit vector `n`,
+// which is r * sqrt(1 - n_i^2)
+pub fn extent(n: &Vec3, radius: f32) -> Vec3 {
+    let extent = |n_i: f32| radius * (1.0 - n_i * n_i).max(0.0).sqrt();
+    Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()))
+}
@@ -1,4 +1,7 @@
-use crate::{aabb, material, ray, vec};
+use crate::{aabb, light_sampler, material, ray, utility::*, vec};
+use rand::prelude::*;
+use std::cmp::Ordering;
+use std::mem;
 use std::rc::Rc;
 
 #[derive(Clone)]
@@ -7,23 +10,37 @@ pub struct HitRecord {
     pub normal: vec::Vec3,
     pub material: Rc<dyn material::Material>,
     pub t: f32,
+    pub u: f32,
+    pub v: f32,
+    // Direction of increasing u on the surface, zero for primitives without a parameterization
+    pub tangent: vec::Vec3,
     pub front_face: bool,
 }
 
+thread_local! {
+    // Material of empty records, shared so that making one doesn't allocate
+    static NO_MATERIAL: Rc<dyn material::Material> = Rc::new(material::Lambertian {
+        albedo: vec::Color::new(0.0, 0.0, 0.0),
+    });
+}
+
 impl HitRecord {
     pub fn empty() -> HitRecord {
         HitRecord {
             p: vec::Point3::empty(),
             normal: vec::Vec3::empty(),
-            material: Rc::new(material::Lambertian {
-                albedo: vec::Color::new(0.0, 0.0, 0.0),
-            }),
+            material: NO_MATERIAL.with(Rc::clone),
             t: f32::MIN,
+            u: 0.0,
+            v: 0.0,
+            tangent: vec::Vec3::empty(),
             front_face: true,
         }
     }
 
+    // Also clears the tangent, primitives with one set it afterwards
     pub fn set_normale_face(&mut self, ray: &ray::Ray, outward_normal: &vec::Vec3) {
+        self.tangent = vec::Vec3::empty();
         self.front_face = vec::dot(&ray.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
             *outward_normal
@@ -37,6 +54,124 @@ pub trait Hittable {
     fn hit(&self, ray: &ray::Ray, t_min: &mut f32, t_max: &mut f32, record: &mut HitRecord)
         -> bool;
     fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool;
+
+    // Fraction of light passing along the ray between `t_min` and `t_max`. Surfaces block it
+    // completely, volumes override this with a lower variance estimate.
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut record = HitRecord::empty();
+        let (mut t_min, mut t_max) = (t_min, t_max);
+        if hit_unmasked(self, ray, &mut t_min, &mut t_max, &mut record) {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    // Whether the object emits light and can be sampled with `random` and `pdf_value`, which
+    // makes it part of the lights sampled directly by the renderer
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // Density, with respect to solid angle at `origin`, of `random` returning `direction`
+    fn pdf_value(&self, _origin: &vec::Point3, _direction: &vec::Vec3) -> f32 {
+        0.0
+    }
+
+    // Direction from `origin` towards a random point of the object
+    fn random(&self, _origin: &vec::Point3, _rng: &mut ThreadRng) -> vec::Vec3 {
+        vec::Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Extent, power and orientation of the light of the object, used to choose between lights
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        None
+    }
+}
+
+// Lets an object be shared, such as a light that is both in the world and in the light list
+impl<T: Hittable + ?Sized> Hittable for Rc<T> {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut HitRecord,
+    ) -> bool {
+        self.as_ref().hit(ray, t_min, t_max, record)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        self.as_ref().bounding_box(t0, t1, output_box)
+    }
+
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        self.as_ref().transmittance(ray, t_min, t_max)
+    }
+
+    fn is_light(&self) -> bool {
+        self.as_ref().is_light()
+    }
+
+    fn pdf_value(&self, origin: &vec::Point3, direction: &vec::Vec3) -> f32 {
+        self.as_ref().pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &vec::Point3, rng: &mut ThreadRng) -> vec::Vec3 {
+        self.as_ref().random(origin, rng)
+    }
+
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        self.as_ref().light_bounds()
+    }
+}
+
+// Relative step past a masked-out hit before looking for the next one
+const MASK_EPSILON: f32 = 0.0001;
+
+// Closest hit of `object` that isn't masked out by the opacity of its material. Partially
+// opaque hits are kept with a probability equal to their opacity, decided by a hash of the ray
+// and the hit so that testing the same hit again, from a list holding the object, agrees.
+pub fn hit_unmasked<H: Hittable + ?Sized>(
+    object: &H,
+    ray: &ray::Ray,
+    t_min: &mut f32,
+    t_max: &mut f32,
+    record: &mut HitRecord,
+) -> bool {
+    // Masked hits go to a separate record to leave the one of an earlier hit untouched
+    let mut temp_record = HitRecord::empty();
+    let mut t_start = *t_min;
+    loop {
+        let mut t_end = *t_max;
+        if !object.hit(ray, &mut t_start, &mut t_end, &mut temp_record) {
+            return false;
+        }
+
+        let opacity = temp_record
+            .material
+            .opacity(temp_record.u, temp_record.v, &temp_record.p);
+        if opacity >= 1.0 || (opacity > 0.0 && hash_hit(ray, temp_record.t) < opacity) {
+            *record = temp_record;
+            return true;
+        }
+        // Relative to the distance so that it still moves past the hit far along the ray
+        t_start = temp_record.t + MASK_EPSILON * temp_record.t.abs().max(1.0);
+    }
+}
+
+// Uniform value in [0, 1) determined by the ray and the distance of a hit along it
+fn hash_hit(ray: &ray::Ray, t: f32) -> f32 {
+    let (o, d) = (ray.origin(), ray.direction());
+    let mut h: u64 = 0x9e37_79b9_7f4a_7c15;
+    for x in [o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), t].iter() {
+        // SplitMix64 finalizer over the bits of each value
+        h = (h ^ x.to_bits() as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+    }
+    (h >> 40) as f32 / (1u64 << 24) as f32
 }
 
 pub struct HittableList {
@@ -55,6 +190,56 @@ impl HittableList {
     pub fn clear(&mut self) {
         self.objects.clear();
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
+
+    // List of the lights among the objects, which stay in this list as handles shared with the
+    // returned one. Lights nested in other objects aren't found.
+    pub fn lights(&mut self) -> HittableList {
+        let mut lights = HittableList::new();
+        for object in self.objects.iter_mut() {
+            if object.is_light() {
+                let shared: Rc<dyn Hittable> =
+                    Rc::from(mem::replace(object, Box::new(HittableList::new())));
+                *object = Box::new(shared.clone());
+                lights.add(Box::new(shared));
+            }
+        }
+        lights
+    }
+
+    // Moves every bounded object into a BVH. Unbounded objects, such as infinite planes, can't
+    // be placed in the hierarchy and stay next to it in the list to be tested on every ray.
+    pub fn into_bvh(self, t0: f32, t1: f32) -> HittableList {
+        let mut bounded = vec![];
+        let mut list = HittableList::new();
+        let mut temp_box = aabb::Aabb::empty();
+
+        for object in self.objects {
+            if object.bounding_box(t0, t1, &mut temp_box) {
+                bounded.push(object);
+            } else {
+                list.add(object);
+            }
+        }
+
+        if !bounded.is_empty() {
+            list.add(Box::new(BvhNode::new(bounded, t0, t1)));
+        }
+        list
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Hittable for HittableList {
@@ -67,10 +252,10 @@ impl Hittable for HittableList {
     ) -> bool {
         let mut temp_record = HitRecord::empty();
         let mut hit_anything = false;
-        let mut closest = t_max;
+        let closest = t_max;
 
         for object in &self.objects {
-            if object.hit(ray, t_min, closest, &mut temp_record) {
+            if hit_unmasked(object.as_ref(), ray, t_min, closest, &mut temp_record) {
                 hit_anything = true;
                 *closest = temp_record.t;
                 *record = temp_record.clone();
@@ -80,6 +265,39 @@ impl Hittable for HittableList {
         hit_anything
     }
 
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut transmittance = 1.0;
+        for object in &self.objects {
+            transmittance *= object.transmittance(ray, t_min, t_max);
+            if transmittance <= 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    // Lists sample each of their objects with the same probability
+    fn pdf_value(&self, origin: &vec::Point3, direction: &vec::Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self
+            .objects
+            .iter()
+            .map(|o| o.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f32
+    }
+
+    fn random(&self, origin: &vec::Point3, rng: &mut ThreadRng) -> vec::Vec3 {
+        if self.objects.is_empty() {
+            return vec::Vec3::new(1.0, 0.0, 0.0);
+        }
+        let i =
+            ((random_double(rng) * self.objects.len() as f32) as usize).min(self.objects.len() - 1);
+        self.objects[i].random(origin, rng)
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
         if self.objects.is_empty() {
             return false;
@@ -108,9 +326,104 @@ pub struct BvhNode {
     left: Box<dyn Hittable>,
     right: Box<dyn Hittable>,
     _box: aabb::Aabb,
+    // Whether the children are the objects themselves rather than nodes, which already leave
+    // out masked hits
+    leaves: bool,
+}
+
+impl BvhNode {
+    pub fn new(mut objects: Vec<Box<dyn Hittable>>, t0: f32, t1: f32) -> BvhNode {
+        let left: Box<dyn Hittable>;
+        let right: Box<dyn Hittable>;
+        let leaves = objects.len() <= 2;
+
+        match objects.len() {
+            0 => panic!("BvhNode::new called with no objects"),
+            1 => {
+                left = objects.pop().unwrap();
+                right = Box::new(HittableList::new());
+            }
+            2 => {
+                right = objects.pop().unwrap();
+                left = objects.pop().unwrap();
+            }
+            _ => {
+                // Split along the axis in which the object centroids are spread the most
+                let centroids: Vec<vec::Point3> = objects
+                    .iter()
+                    .map(|o| {
+                        let b = bounded_box(o.as_ref(), t0, t1);
+                        (b.min() + b.max()) * 0.5
+                    })
+                    .collect();
+                let bounds = centroids
+                    .iter()
+                    .fold(aabb::Aabb::new(centroids[0], centroids[0]), |b, c| {
+                        aabb::surrounding_box(&b, &aabb::Aabb::new(*c, *c))
+                    });
+                let extent = bounds.max() - bounds.min();
+                let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+                    0
+                } else if extent.y() > extent.z() {
+                    1
+                } else {
+                    2
+                };
+
+                let mut keyed: Vec<(f32, Box<dyn Hittable>)> = centroids
+                    .iter()
+                    .map(|c| c[axis])
+                    .zip(objects.drain(..))
+                    .collect();
+                keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+                let mut sorted: Vec<Box<dyn Hittable>> = keyed.into_iter().map(|k| k.1).collect();
+
+                let upper = sorted.split_off(sorted.len() / 2);
+                left = Box::new(BvhNode::new(sorted, t0, t1));
+                right = Box::new(BvhNode::new(upper, t0, t1));
+            }
+        }
+
+        let left_box = bounded_box(left.as_ref(), t0, t1);
+        let mut right_box = aabb::Aabb::empty();
+        let _box = if right.bounding_box(t0, t1, &mut right_box) {
+            aabb::surrounding_box(&left_box, &right_box)
+        } else {
+            left_box
+        };
+
+        BvhNode {
+            left,
+            right,
+            _box,
+            leaves,
+        }
+    }
+
+    // Masked hits are left out once, by the nodes holding the objects
+    fn hit_child(
+        &self,
+        child: &dyn Hittable,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut HitRecord,
+    ) -> bool {
+        if self.leaves {
+            hit_unmasked(child, ray, t_min, t_max, record)
+        } else {
+            child.hit(ray, t_min, t_max, record)
+        }
+    }
 }
 
-impl BvhNode {}
+fn bounded_box(object: &dyn Hittable, t0: f32, t1: f32) -> aabb::Aabb {
+    let mut output_box = aabb::Aabb::empty();
+    if !object.bounding_box(t0, t1, &mut output_box) {
+        panic!("No bounding box in BvhNode::new");
+    }
+    output_box
+}
 
 impl Hittable for BvhNode {
     fn hit(
@@ -120,17 +433,116 @@ impl Hittable for BvhNode {
         t_max: &mut f32,
         record: &mut HitRecord,
     ) -> bool {
-        if !self._box.hit(ray, t_min, t_max) {
+        let (mut box_min, mut box_max) = (*t_min, *t_max);
+        if !self._box.hit(ray, &mut box_min, &mut box_max) {
             return false;
         }
 
-        let hit_left = self.left.hit(ray, t_min, t_max, record);
-        let hit_right = self.left.hit(ray, t_min, t_max, record);
+        let hit_left = self.hit_child(self.left.as_ref(), ray, t_min, t_max, record);
+        let mut right_max = if hit_left { record.t } else { *t_max };
+        let hit_right = self.hit_child(self.right.as_ref(), ray, t_min, &mut right_max, record);
 
         hit_left || hit_right
     }
 
-    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
-        unimplemented!();
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        let (mut box_min, mut box_max) = (t_min, t_max);
+        if !self._box.hit(ray, &mut box_min, &mut box_max) {
+            return 1.0;
+        }
+
+        let transmittance = self.left.transmittance(ray, t_min, t_max);
+        if transmittance <= 0.0 {
+            return 0.0;
+        }
+        transmittance * self.right.transmittance(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = self._box;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plane::Plane;
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+
+    fn square(z: f32, material: Rc<dyn material::Material>) -> Box<dyn Hittable> {
+        Box::new(Quad::new(
+            vec::Point3::new(-1.0, -1.0, z),
+            vec::Vec3::new(2.0, 0.0, 0.0),
+            vec::Vec3::new(0.0, 2.0, 0.0),
+            material,
+        ))
+    }
+
+    #[test]
+    fn bvh_skips_masked_hits() {
+        let opaque: Rc<dyn material::Material> = Rc::new(material::Lambertian {
+            albedo: vec::Color::new(0.5, 0.5, 0.5),
+        });
+        let clear = Rc::new(material::Masked::new(
+            opaque.clone(),
+            Rc::new(SolidColor::scalar(0.0)),
+        ));
+
+        // Enough objects for the squares to sit under inner nodes
+        let mut world = HittableList::new();
+        world.add(square(-1.0, clear));
+        world.add(square(-2.0, opaque.clone()));
+        for i in 0..6 {
+            let center = vec::Point3::new(5.0 + i as f32, 0.0, -3.0);
+            world.add(Box::new(Sphere::new(center, 0.5, opaque.clone())));
+        }
+        let world = world.into_bvh(0.0, 1.0);
+
+        let ray = ray::Ray::new(
+            vec::Point3::new(0.0, 0.0, 0.0),
+            vec::Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let mut record = HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        assert!(world.hit(&ray, &mut t_min, &mut t_max, &mut record));
+        assert!((record.t - 2.0).abs() < 1e-4);
+        assert_eq!(world.transmittance(&ray, 0.001, 1.5), 1.0);
+        assert_eq!(world.transmittance(&ray, 0.001, INFINITY), 0.0);
+    }
+
+    #[test]
+    fn planes_stay_next_to_the_bvh() {
+        let material: Rc<dyn material::Material> = Rc::new(material::Lambertian {
+            albedo: vec::Color::new(0.5, 0.5, 0.5),
+        });
+        let mut world = HittableList::new();
+        world.add(Box::new(Plane::new(
+            vec::Point3::new(0.0, 0.0, 0.0),
+            vec::Vec3::new(0.0, 1.0, 0.0),
+            material.clone(),
+        )));
+        for i in 0..5 {
+            let center = vec::Point3::new(3.0 * i as f32, 1.0, 0.0);
+            world.add(Box::new(Sphere::new(center, 0.5, material.clone())));
+        }
+        let world = world.into_bvh(0.0, 1.0);
+
+        let closest = |origin: vec::Point3| {
+            let ray = ray::Ray::new(origin, vec::Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let mut record = HitRecord::empty();
+            let (mut t_min, mut t_max) = (0.001, INFINITY);
+            assert!(world.hit(&ray, &mut t_min, &mut t_max, &mut record));
+            record.p.y()
+        };
+        // Onto a sphere, and onto the plane between and far beyond them
+        for i in 0..5 {
+            assert!((closest(vec::Point3::new(3.0 * i as f32, 5.0, 0.0)) - 1.5).abs() < 1e-4);
+        }
+        assert!(closest(vec::Point3::new(1.5, 5.0, 0.0)).abs() < 1e-4);
+        assert!(closest(vec::Point3::new(-1000.0, 5.0, 1000.0)).abs() < 1e-3);
     }
 }
@@ -0,0 +1,196 @@
+use crate::{aabb, hittable, light_sampler, ray, vec::*};
+use rand::prelude::*;
+
+// Moves the wrapped object by an offset, which can change linearly over the shutter interval
+pub struct Translate {
+    object: Box<dyn hittable::Hittable>,
+    offset0: Vec3,
+    offset1: Vec3,
+    time0: f32,
+    time1: f32,
+}
+
+impl Translate {
+    pub fn new(object: Box<dyn hittable::Hittable>, offset: Vec3) -> Translate {
+        Translate::moving(object, offset, offset, 0.0, 1.0)
+    }
+
+    pub fn moving(
+        object: Box<dyn hittable::Hittable>,
+        offset0: Vec3,
+        offset1: Vec3,
+        time0: f32,
+        time1: f32,
+    ) -> Translate {
+        Translate {
+            object,
+            offset0,
+            offset1,
+            time0,
+            time1,
+        }
+    }
+
+    pub fn offset(&self, time: f32) -> Vec3 {
+        if self.time1 == self.time0 {
+            return self.offset0;
+        }
+        self.offset0
+            + (self.offset1 - self.offset0) * ((time - self.time0) / (self.time1 - self.time0))
+    }
+
+    // Ray in the space of the wrapped object
+    fn moved(&self, ray: &ray::Ray) -> ray::Ray {
+        ray.spawn(ray.origin() - self.offset(ray.time()), ray.direction())
+    }
+
+    // Box covering `b` over the whole motion, which is linear
+    fn moved_box(&self, b: &aabb::Aabb, t0: f32, t1: f32) -> aabb::Aabb {
+        let (start, end) = (self.offset(t0), self.offset(t1));
+        aabb::surrounding_box(
+            &aabb::Aabb::new(b.min() + start, b.max() + start),
+            &aabb::Aabb::new(b.min() + end, b.max() + end),
+        )
+    }
+}
+
+impl hittable::Hittable for Translate {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        if !self.object.hit(&self.moved(ray), t_min, t_max, record) {
+            return false;
+        }
+
+        record.p = record.p + self.offset(ray.time());
+        true
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        if !self.object.bounding_box(t0, t1, output_box) {
+            return false;
+        }
+
+        // Motion is linear, so the boxes at the shutter ends enclose the whole path
+        *output_box = self.moved_box(output_box, t0, t1);
+        true
+    }
+
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        self.object.transmittance(&self.moved(ray), t_min, t_max)
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    // Light samples don't have a time, so moving lights are sampled where they start
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        self.object
+            .pdf_value(&(*origin - self.offset(self.time0)), direction)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut ThreadRng) -> Vec3 {
+        self.object
+            .random(&(*origin - self.offset(self.time0)), rng)
+    }
+
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        let mut bounds = self.object.light_bounds()?;
+        bounds.bounds = self.moved_box(&bounds.bounds, self.time0, self.time1);
+        Some(bounds)
+    }
+}
+
+// Turns the wrapped object around, so that its outward normal points the other way
+pub struct FlipFace {
+    object: Box<dyn hittable::Hittable>,
+}
+
+impl FlipFace {
+    pub fn new(object: Box<dyn hittable::Hittable>) -> FlipFace {
+        FlipFace { object }
+    }
+}
+
+impl hittable::Hittable for FlipFace {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        if !self.object.hit(ray, t_min, t_max, record) {
+            return false;
+        }
+
+        // The normal already faces the ray, only the side it came from changes
+        record.front_face = !record.front_face;
+        true
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        self.object.bounding_box(t0, t1, output_box)
+    }
+
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        self.object.transmittance(ray, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant_medium::ConstantMedium;
+    use crate::hittable::Hittable;
+    use crate::material;
+    use crate::sphere::Sphere;
+    use crate::utility::INFINITY;
+    use std::rc::Rc;
+
+    fn unit_sphere(material: Rc<dyn material::Material>) -> Box<dyn hittable::Hittable> {
+        Box::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material))
+    }
+
+    #[test]
+    fn translated_lights_are_sampled_where_they_are() {
+        let mut rng = rand::thread_rng();
+        let light = material::DiffuseLight::new(Color::new(4.0, 4.0, 4.0));
+        let offset = Vec3::new(0.0, 5.0, 0.0);
+        let moved = Translate::new(unit_sphere(Rc::new(light)), offset);
+        assert!(moved.is_light());
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        for _ in 0..100 {
+            let direction = unit_vector(moved.random(&origin, &mut rng));
+            assert!(dot(&direction, &unit_vector(offset)) > 0.97);
+            assert!(moved.pdf_value(&origin, &direction) > 0.0);
+        }
+        assert_eq!(moved.pdf_value(&origin, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+
+        let bounds = moved.light_bounds().unwrap().bounds;
+        assert_eq!(bounds.min().y(), 4.0);
+        assert_eq!(bounds.max().y(), 6.0);
+    }
+
+    #[test]
+    fn translated_volumes_let_light_through() {
+        let boundary = unit_sphere(Rc::new(material::Lambertian::new(Color::empty())));
+        let fog = ConstantMedium::new(boundary, 0.5, Color::new(1.0, 1.0, 1.0));
+        let moved = Translate::new(Box::new(fog), Vec3::new(3.0, 0.0, 0.0));
+
+        // Through the middle, two units of fog
+        let ray = ray::Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let transmittance = moved.transmittance(&ray, 0.001, INFINITY);
+        assert!((transmittance - (-1.0f32).exp()).abs() < 1e-4);
+
+        // Where the fog would be without the offset
+        let ray = ray::Ray::new(Point3::new(0.0, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert_eq!(moved.transmittance(&ray, 0.001, INFINITY), 1.0);
+    }
+}
@@ -0,0 +1,31 @@
+pub mod aabb;
+pub mod aarect;
+pub mod atmosphere;
+pub mod box_shape;
+pub mod camera;
+pub mod color;
+pub mod cone;
+pub mod constant_medium;
+pub mod cylinder;
+pub mod disk;
+pub mod hittable;
+pub mod instance;
+pub mod light;
+pub mod light_sampler;
+pub mod material;
+pub mod microfacet;
+pub mod moving_sphere;
+pub mod onb;
+pub mod plane;
+pub mod principled;
+pub mod quad;
+pub mod ray;
+pub mod sky;
+pub mod spectrum;
+pub mod sphere;
+pub mod texture;
+pub mod torus;
+pub mod triangle;
+pub mod utility;
+pub mod vec;
+pub mod volume;
@@ -0,0 +1,234 @@
+use crate::onb;
+use crate::utility::*;
+use crate::vec::{self, Color, Point3, Vec3};
+use rand::prelude::*;
+
+// Light arriving at a point from a sampled point of a light, before any occlusion
+pub struct LightSample {
+    // Unit direction towards the light
+    pub direction: Vec3,
+    // Distance to the sampled point, infinite for lights in the distance
+    pub distance: f32,
+    // Incident radiance, or irradiance for lights of no size
+    pub radiance: Color,
+    // Density of the direction with respect to solid angle, none for lights of no size, which
+    // only shadow rays can reach
+    pub pdf: Option<f32>,
+}
+
+// Light source that isn't part of the geometry, reached by shadow rays towards it
+pub trait Light {
+    fn sample(&self, p: &Point3, rng: &mut ThreadRng) -> Option<LightSample>;
+
+    // Radiance of rays escaping the scene along the unit `direction`, for distant lights with a
+    // size that scattered rays can also find
+    fn emitted(&self, _direction: &Vec3) -> Color {
+        Color::empty()
+    }
+
+    // Density of `sample` giving the unit `direction` from `p`
+    fn pdf(&self, _p: &Point3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+}
+
+// Light from a single point, its intensity decreasing with the squared distance
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color,
+    // Distance at which the light fades out completely, if any
+    pub range: Option<f32>,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+            range: None,
+        }
+    }
+
+    // Smoothly fades the light out up to `range` so it doesn't reach far away surfaces
+    pub fn with_range(mut self, range: f32) -> PointLight {
+        self.range = Some(range);
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3, _rng: &mut ThreadRng) -> Option<LightSample> {
+        let (direction, distance) = towards(p, &self.position)?;
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * falloff(distance, self.range),
+            pdf: None,
+        })
+    }
+}
+
+// Point light shining in a cone, at full intensity inside of `inner_angle` off its axis and
+// fading out up to `outer_angle`
+pub struct SpotLight {
+    pub position: Point3,
+    pub axis: Vec3,
+    pub intensity: Color,
+    pub cos_inner: f32,
+    pub cos_outer: f32,
+    pub range: Option<f32>,
+}
+
+impl SpotLight {
+    // Angles are in degrees
+    pub fn new(
+        position: Point3,
+        target: Point3,
+        intensity: Color,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> SpotLight {
+        let outer_angle = outer_angle.max(inner_angle);
+        SpotLight {
+            position,
+            axis: vec::unit_vector(target - position),
+            intensity,
+            cos_inner: degrees_to_radians(inner_angle).cos(),
+            cos_outer: degrees_to_radians(outer_angle).cos(),
+            range: None,
+        }
+    }
+
+    pub fn with_range(mut self, range: f32) -> SpotLight {
+        self.range = Some(range);
+        self
+    }
+
+    // Smoothstep from the outer cone to the inner one
+    fn cone(&self, cos: f32) -> f32 {
+        if cos >= self.cos_inner {
+            return 1.0;
+        }
+        if cos <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3, _rng: &mut ThreadRng) -> Option<LightSample> {
+        let (direction, distance) = towards(p, &self.position)?;
+        let cone = self.cone(vec::dot(&-direction, &self.axis));
+        if cone <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * (cone * falloff(distance, self.range)),
+            pdf: None,
+        })
+    }
+}
+
+// Light from a distant disc such as the sun, covering `angular_diameter` degrees of the sky in
+// the given direction. Its irradiance is given on a surface facing it, a zero diameter makes
+// it a pure directional light with sharp shadows.
+pub struct DirectionalLight {
+    // Unit direction towards the light
+    pub direction: Vec3,
+    pub irradiance: Color,
+    // One minus the cosine of the angular radius
+    cone_height: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color, angular_diameter: f32) -> DirectionalLight {
+        let half_angle = degrees_to_radians(angular_diameter.max(0.0) / 2.0);
+        // Stable form of 1 - cos for the small angles of real suns
+        let sin_half = (half_angle / 2.0).sin();
+        DirectionalLight {
+            direction: vec::unit_vector(direction),
+            irradiance,
+            cone_height: 2.0 * sin_half * sin_half,
+        }
+    }
+
+    fn solid_angle(&self) -> f32 {
+        2.0 * PI * self.cone_height
+    }
+
+    fn covers(&self, direction: &Vec3) -> bool {
+        self.cone_height > 0.0 && 1.0 - vec::dot(direction, &self.direction) <= self.cone_height
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Point3, rng: &mut ThreadRng) -> Option<LightSample> {
+        if self.cone_height <= 0.0 {
+            return Some(LightSample {
+                direction: self.direction,
+                distance: INFINITY,
+                radiance: self.irradiance,
+                pdf: None,
+            });
+        }
+
+        let one_minus_cos = random_double(rng) * self.cone_height;
+        let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double(rng);
+        let direction = onb::Onb::build_from_w(&self.direction).local(&Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            1.0 - one_minus_cos,
+        ));
+        Some(LightSample {
+            direction,
+            distance: INFINITY,
+            radiance: self.irradiance / self.solid_angle(),
+            pdf: Some(1.0 / self.solid_angle()),
+        })
+    }
+
+    fn emitted(&self, direction: &Vec3) -> Color {
+        if self.covers(direction) {
+            self.irradiance / self.solid_angle()
+        } else {
+            Color::empty()
+        }
+    }
+
+    fn pdf(&self, _p: &Point3, direction: &Vec3) -> f32 {
+        if self.covers(direction) {
+            1.0 / self.solid_angle()
+        } else {
+            0.0
+        }
+    }
+}
+
+// Unit direction and distance from `p` to `position`, none if they coincide
+fn towards(p: &Point3, position: &Point3) -> Option<(Vec3, f32)> {
+    let offset = *position - *p;
+    let distance = offset.length();
+    if distance <= 0.0 {
+        return None;
+    }
+    Some((offset / distance, distance))
+}
+
+// Inverse square falloff, windowed to reach zero at `range` as in Karis 2013, "Real Shading in
+// Unreal Engine 4"
+fn falloff(distance: f32, range: Option<f32>) -> f32 {
+    let inverse_square = 1.0 / (distance * distance);
+    match range {
+        Some(range) => {
+            let ratio = distance / range;
+            let window = (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0);
+            inverse_square * window * window
+        }
+        None => inverse_square,
+    }
+}
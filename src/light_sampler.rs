@@ -0,0 +1,647 @@
+// Choice of the emissive object to sample for the direct lighting of a point
+use crate::aabb::{self, Aabb};
+use crate::color;
+use crate::hittable::{Hittable, HittableList};
+use crate::ray;
+use crate::utility::*;
+use crate::vec::{self, Color, Point3, Vec3};
+use rand::prelude::*;
+use std::cmp::Ordering;
+
+// Buckets per axis when looking for the best split of the light tree
+const SPLIT_BUCKETS: usize = 12;
+
+#[derive(Clone, Copy)]
+pub enum LightSelection {
+    // Every light with the same probability
+    Uniform,
+    // Lights in proportion to the power they emit
+    Power,
+    // Lights in proportion to an estimate of what they contribute to the point, given by their
+    // power, distance and orientation, by walking down a hierarchy of their bounds. After Conty
+    // Estevez and Kulla 2018, "Importance Sampling of Many Lights with Adaptive Tree Splitting".
+    Tree,
+}
+
+// Cone of directions around a unit `axis`
+#[derive(Clone, Copy)]
+pub struct DirectionCone {
+    pub axis: Vec3,
+    pub cos_theta: f32,
+}
+
+impl DirectionCone {
+    pub fn new(axis: Vec3, cos_theta: f32) -> DirectionCone {
+        DirectionCone {
+            axis: vec::unit_vector(axis),
+            cos_theta,
+        }
+    }
+
+    pub fn entire_sphere() -> DirectionCone {
+        DirectionCone::new(Vec3::new(0.0, 0.0, 1.0), -1.0)
+    }
+
+    // Smallest cone holding both
+    pub fn union(a: &DirectionCone, b: &DirectionCone) -> DirectionCone {
+        let theta_a = safe_acos(a.cos_theta);
+        let theta_b = safe_acos(b.cos_theta);
+        let theta_d = safe_acos(vec::dot(&a.axis, &b.axis));
+        if (theta_d + theta_b).min(PI) <= theta_a {
+            return *a;
+        }
+        if (theta_d + theta_a).min(PI) <= theta_b {
+            return *b;
+        }
+
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        let rotation_axis = vec::cross(&a.axis, &b.axis);
+        if theta_o >= PI || rotation_axis.length_squared() == 0.0 {
+            return DirectionCone::entire_sphere();
+        }
+        // Turn the axis of `a` towards the one of `b` until the cone reaches both
+        let axis = rotate(&a.axis, &vec::unit_vector(rotation_axis), theta_o - theta_a);
+        DirectionCone::new(axis, theta_o.cos())
+    }
+}
+
+// Spatial and directional extent of the light emitted by a group of lights
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub bounds: Aabb,
+    pub power: f32,
+    // Directions of the surface normals of the emitters
+    pub normals: DirectionCone,
+    // Cosine of the largest angle to the normal at which light leaves the surfaces
+    pub cos_theta_e: f32,
+    // Whether surfaces emit on both sides of their normal
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    // Flat emitter of uniform `radiance`, emitting on both sides like the emissive materials do
+    pub fn flat(bounds: Aabb, normal: Vec3, area: f32, radiance: &Color) -> LightBounds {
+        LightBounds {
+            bounds,
+            power: 2.0 * PI * area * color::luminance(radiance),
+            normals: DirectionCone::new(normal, 1.0),
+            cos_theta_e: 0.0,
+            two_sided: true,
+        }
+    }
+
+    // Sphere of uniform `radiance`, emitting in every direction
+    pub fn sphere(bounds: Aabb, radius: f32, radiance: &Color) -> LightBounds {
+        LightBounds {
+            bounds,
+            power: PI * 4.0 * PI * radius * radius * color::luminance(radiance),
+            normals: DirectionCone::entire_sphere(),
+            cos_theta_e: 0.0,
+            two_sided: false,
+        }
+    }
+
+    pub fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        LightBounds {
+            bounds: aabb::surrounding_box(&a.bounds, &b.bounds),
+            power: a.power + b.power,
+            normals: DirectionCone::union(&a.normals, &b.normals),
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+
+    fn centroid(&self) -> Point3 {
+        (self.bounds.min() + self.bounds.max()) * 0.5
+    }
+
+    // Conservative estimate of the light reaching `p`: the power over the squared distance,
+    // times the cosine at the emitters of the smallest angle the bounds allow
+    pub fn importance(&self, p: &Point3) -> f32 {
+        let center = self.centroid();
+        let offset = *p - center;
+        let radius = (self.bounds.max() - self.bounds.min()).length() / 2.0;
+        let distance_squared = offset.length_squared();
+        if distance_squared == 0.0 {
+            return self.power / radius.max(f32::MIN_POSITIVE);
+        }
+
+        let wi = offset / distance_squared.sqrt();
+        let mut cos_theta_w = vec::dot(&self.normals.axis, &wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+
+        // Half-angle of the bounding sphere seen from `p`
+        let cos_theta_b = if distance_squared < radius * radius {
+            -1.0
+        } else {
+            safe_sqrt(1.0 - radius * radius / distance_squared)
+        };
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        // Angle between `wi` and the closest normal, reduced by the extent of the bounds
+        let cos_theta_o = self.normals.cos_theta;
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o * cos_theta_o);
+        let (sin_x, cos_x) = angle_difference(sin_theta_w, cos_theta_w, sin_theta_o, cos_theta_o);
+        let (_, cos_theta) = angle_difference(sin_x, cos_x, sin_theta_b, cos_theta_b);
+        if cos_theta <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        // Distances within the bounds would make the estimate arbitrarily large
+        self.power * cos_theta / distance_squared.max(radius)
+    }
+}
+
+enum LightNode {
+    Leaf {
+        bounds: LightBounds,
+        light: usize,
+    },
+    Interior {
+        bounds: LightBounds,
+        children: [usize; 2],
+    },
+}
+
+impl LightNode {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } => bounds,
+            LightNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Lights sampled directly by the renderer, chosen for each point with a `LightSelection`. Lights
+// without `light_bounds` can only be sampled uniformly and are otherwise left to be found by
+// scattering.
+pub struct LightSampler {
+    lights: Vec<Box<dyn Hittable>>,
+    selection: LightSelection,
+    // Probability of each light for uniform and power selection
+    probabilities: Vec<f32>,
+    cdf: Vec<f32>,
+    // Light tree with its root first
+    nodes: Vec<LightNode>,
+}
+
+impl LightSampler {
+    pub fn new(lights: HittableList, selection: LightSelection) -> LightSampler {
+        let lights = lights.into_objects();
+        let bounds: Vec<Option<LightBounds>> = lights
+            .iter()
+            .map(|l| l.light_bounds().filter(|b| b.power > 0.0))
+            .collect();
+
+        let total: f32 = bounds.iter().flatten().map(|b| b.power).sum();
+        let probabilities: Vec<f32> = match selection {
+            LightSelection::Power if total > 0.0 => bounds
+                .iter()
+                .map(|b| b.map_or(0.0, |b| b.power / total))
+                .collect(),
+            _ => vec![1.0 / lights.len() as f32; lights.len()],
+        };
+        let cdf = probabilities
+            .iter()
+            .scan(0.0, |sum, p| {
+                *sum += p;
+                Some(*sum)
+            })
+            .collect();
+
+        let mut nodes = vec![];
+        if let LightSelection::Tree = selection {
+            let mut items: Vec<(usize, LightBounds)> = bounds
+                .iter()
+                .enumerate()
+                .filter_map(|(i, b)| b.map(|b| (i, b)))
+                .collect();
+            if !items.is_empty() {
+                build_tree(&mut nodes, &mut items);
+            }
+        }
+
+        LightSampler {
+            lights,
+            selection,
+            probabilities,
+            cdf,
+            nodes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // Direction from `origin` towards a point of a chosen light, none if no light can be chosen
+    pub fn random(&self, origin: &Point3, rng: &mut ThreadRng) -> Option<Vec3> {
+        let light = self.pick(origin, rng)?;
+        Some(self.lights[light].random(origin, rng))
+    }
+
+    // Index of the light chosen for `origin`
+    fn pick(&self, origin: &Point3, rng: &mut ThreadRng) -> Option<usize> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        match self.selection {
+            LightSelection::Tree => self.pick_from_tree(origin, rng),
+            _ => {
+                let u = random_double(rng) * self.cdf[self.cdf.len() - 1];
+                let light = self.cdf.partition_point(|&c| c <= u);
+                Some(light.min(self.lights.len() - 1))
+            }
+        }
+    }
+
+    // Density, with respect to solid angle at `origin`, of `random` returning `direction`
+    pub fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        match self.selection {
+            LightSelection::Tree => {
+                if self.nodes.is_empty() {
+                    return 0.0;
+                }
+                let ray = ray::Ray::new(*origin, *direction, 0.0);
+                self.tree_pdf(0, 1.0, &ray)
+            }
+            _ => self
+                .lights
+                .iter()
+                .zip(&self.probabilities)
+                .filter(|(_, &p)| p > 0.0)
+                .map(|(l, p)| p * l.pdf_value(origin, direction))
+                .sum(),
+        }
+    }
+
+    fn pick_from_tree(&self, origin: &Point3, rng: &mut ThreadRng) -> Option<usize> {
+        if self.nodes.is_empty() || self.nodes[0].bounds().importance(origin) <= 0.0 {
+            return None;
+        }
+        let mut node = 0;
+        loop {
+            match &self.nodes[node] {
+                LightNode::Leaf { light, .. } => return Some(*light),
+                LightNode::Interior { children, .. } => {
+                    let [left, right] = self.child_probabilities(children, origin)?;
+                    node = if random_double(rng) * (left + right) < left {
+                        children[0]
+                    } else {
+                        children[1]
+                    };
+                }
+            }
+        }
+    }
+
+    // Sums the densities of the lights under `node` that the ray may hit, each weighted by the
+    // probability of picking it from `origin`
+    fn tree_pdf(&self, node: usize, probability: f32, ray: &ray::Ray) -> f32 {
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if !self.nodes[node]
+            .bounds()
+            .bounds
+            .hit(ray, &mut t_min, &mut t_max)
+        {
+            return 0.0;
+        }
+        match &self.nodes[node] {
+            LightNode::Leaf { light, .. } => {
+                probability * self.lights[*light].pdf_value(&ray.origin(), &ray.direction())
+            }
+            LightNode::Interior { children, .. } => {
+                let [left, right] = match self.child_probabilities(children, &ray.origin()) {
+                    Some(probabilities) => probabilities,
+                    None => return 0.0,
+                };
+                let mut pdf = 0.0;
+                if left > 0.0 {
+                    pdf += self.tree_pdf(children[0], probability * left, ray);
+                }
+                if right > 0.0 {
+                    pdf += self.tree_pdf(children[1], probability * right, ray);
+                }
+                pdf
+            }
+        }
+    }
+
+    // Probabilities of going down each child from `origin`, none if neither can contribute
+    fn child_probabilities(&self, children: &[usize; 2], origin: &Point3) -> Option<[f32; 2]> {
+        let left = self.nodes[children[0]].bounds().importance(origin);
+        let right = self.nodes[children[1]].bounds().importance(origin);
+        let total = left + right;
+        if total <= 0.0 || total.is_nan() {
+            return None;
+        }
+        Some([left / total, right / total])
+    }
+}
+
+// Builds the subtree over `items` into `nodes`, splitting them where the surface area
+// orientation heuristic is the lowest, and returns the index of its root
+fn build_tree(nodes: &mut Vec<LightNode>, items: &mut [(usize, LightBounds)]) -> usize {
+    let bounds = items
+        .iter()
+        .skip(1)
+        .fold(items[0].1, |b, (_, item)| LightBounds::union(&b, item));
+    if items.len() == 1 {
+        nodes.push(LightNode::Leaf {
+            bounds,
+            light: items[0].0,
+        });
+        return nodes.len() - 1;
+    }
+
+    let mut centroid_min = items[0].1.centroid();
+    let mut centroid_max = centroid_min;
+    for (_, item) in items.iter() {
+        let c = item.centroid();
+        centroid_min = Point3::new(
+            centroid_min.x().min(c.x()),
+            centroid_min.y().min(c.y()),
+            centroid_min.z().min(c.z()),
+        );
+        centroid_max = Point3::new(
+            centroid_max.x().max(c.x()),
+            centroid_max.y().max(c.y()),
+            centroid_max.z().max(c.z()),
+        );
+    }
+
+    let diagonal = bounds.bounds.max() - bounds.bounds.min();
+    let max_extent = diagonal.x().max(diagonal.y()).max(diagonal.z());
+    let mut best: Option<(f32, usize, usize)> = None;
+    for axis in 0..3 {
+        let extent = centroid_max[axis] - centroid_min[axis];
+        if extent <= 0.0 {
+            continue;
+        }
+        let mut buckets: Vec<Option<LightBounds>> = vec![None; SPLIT_BUCKETS];
+        for (_, item) in items.iter() {
+            let bucket = &mut buckets[bucket(item, axis, centroid_min[axis], extent)];
+            *bucket = Some(bucket.map_or(*item, |b| LightBounds::union(&b, item)));
+        }
+
+        // Long and thin nodes are penalized for splitting along their short axes
+        let regularization = max_extent / diagonal[axis];
+        for split in 1..SPLIT_BUCKETS {
+            let cost = |range: &[Option<LightBounds>]| {
+                range
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .reduce(|a, b| LightBounds::union(&a, &b))
+                    .map_or(0.0, |b| split_cost(&b))
+            };
+            let total = regularization * (cost(&buckets[..split]) + cost(&buckets[split..]));
+            if best.map_or(true, |(c, _, _)| total < c) {
+                best = Some((total, axis, split));
+            }
+        }
+    }
+
+    // Sort along the best axis and cut before its first bucket above the split, or in the middle
+    // when the centroids can't be told apart
+    let mut middle = items.len() / 2;
+    if let Some((_, axis, split)) = best {
+        items.sort_by(|a, b| {
+            a.1.centroid()[axis]
+                .partial_cmp(&b.1.centroid()[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+        let extent = centroid_max[axis] - centroid_min[axis];
+        let below = items
+            .iter()
+            .take_while(|(_, b)| bucket(b, axis, centroid_min[axis], extent) < split)
+            .count();
+        if below > 0 && below < items.len() {
+            middle = below;
+        }
+    }
+
+    // The node is pushed first so that the root ends up at index 0
+    nodes.push(LightNode::Leaf { bounds, light: 0 });
+    let index = nodes.len() - 1;
+    let (left_items, right_items) = items.split_at_mut(middle);
+    let left = build_tree(nodes, left_items);
+    let right = build_tree(nodes, right_items);
+    nodes[index] = LightNode::Interior {
+        bounds,
+        children: [left, right],
+    };
+    index
+}
+
+// Bucket of the centroid of `bounds` along `axis`, for centroids starting at `min`
+fn bucket(bounds: &LightBounds, axis: usize, min: f32, extent: f32) -> usize {
+    let t = (bounds.centroid()[axis] - min) / extent;
+    ((t * SPLIT_BUCKETS as f32) as usize).min(SPLIT_BUCKETS - 1)
+}
+
+// Surface area orientation heuristic of a node, its power times the measure of the directions it
+// emits in times the surface area of its bounds
+fn split_cost(bounds: &LightBounds) -> f32 {
+    let theta_o = safe_acos(bounds.normals.cos_theta);
+    let theta_e = safe_acos(bounds.cos_theta_e);
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_theta_o = theta_o.sin();
+    let m_omega = 2.0 * PI * (1.0 - theta_o.cos())
+        + PI / 2.0
+            * (2.0 * theta_w * sin_theta_o
+                - (theta_o - 2.0 * theta_w).cos()
+                - 2.0 * theta_o * sin_theta_o
+                + theta_o.cos());
+
+    let d = bounds.bounds.max() - bounds.bounds.min();
+    let area = 2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x());
+    bounds.power * m_omega * area
+}
+
+// Sine and cosine of the difference of two angles, clamped to zero when `b` is larger
+fn angle_difference(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> (f32, f32) {
+    if cos_a > cos_b {
+        return (0.0, 1.0);
+    }
+    (sin_a * cos_b - cos_a * sin_b, cos_a * cos_b + sin_a * sin_b)
+}
+
+// Rotates `v` around the unit `axis` by `angle` radians, with Rodrigues' formula
+fn rotate(v: &Vec3, axis: &Vec3, angle: f32) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    *v * cos + vec::cross(axis, v) * sin + *axis * (vec::dot(axis, v) * (1.0 - cos))
+}
+
+fn safe_acos(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0).acos()
+}
+
+fn safe_sqrt(x: f32) -> f32 {
+    x.max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use crate::sphere::Sphere;
+    use std::rc::Rc;
+
+    // Spheres of different sizes and brightness, apart from each other
+    fn spheres() -> Vec<(Point3, f32, f32)> {
+        vec![
+            (Point3::new(-3.0, 2.0, 0.0), 0.5, 4.0),
+            (Point3::new(3.0, 2.0, 1.0), 0.3, 20.0),
+            (Point3::new(0.0, 4.0, -3.0), 0.8, 1.0),
+            (Point3::new(1.0, 1.0, 4.0), 0.2, 8.0),
+            (Point3::new(-2.0, 5.0, 3.0), 0.6, 2.0),
+        ]
+    }
+
+    fn sampler(selection: LightSelection) -> LightSampler {
+        let mut lights = HittableList::new();
+        for (center, radius, radiance) in spheres() {
+            let material = Rc::new(DiffuseLight::new(Color::new(radiance, radiance, radiance)));
+            lights.add(Box::new(Sphere::new(center, radius, material)));
+        }
+        LightSampler::new(lights, selection)
+    }
+
+    fn shading_points() -> Vec<Point3> {
+        vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(-2.5, 1.0, 0.5),
+            Point3::new(4.0, -1.0, -2.0),
+            Point3::new(0.5, 3.0, 1.0),
+        ]
+    }
+
+    // Probability of picking each light from `origin`, from the weights of the strategy or by
+    // multiplying the probabilities along the way down the tree
+    fn light_probabilities(sampler: &LightSampler, origin: &Point3) -> Vec<f32> {
+        let mut probabilities = vec![0.0; sampler.lights.len()];
+        match sampler.selection {
+            LightSelection::Tree => descend(sampler, 0, 1.0, origin, &mut probabilities),
+            _ => probabilities.copy_from_slice(&sampler.probabilities),
+        }
+        probabilities
+    }
+
+    fn descend(
+        sampler: &LightSampler,
+        node: usize,
+        probability: f32,
+        origin: &Point3,
+        probabilities: &mut [f32],
+    ) {
+        match &sampler.nodes[node] {
+            LightNode::Leaf { light, .. } => probabilities[*light] += probability,
+            LightNode::Interior { children, .. } => {
+                if let Some([left, right]) = sampler.child_probabilities(children, origin) {
+                    descend(
+                        sampler,
+                        children[0],
+                        probability * left,
+                        origin,
+                        probabilities,
+                    );
+                    descend(
+                        sampler,
+                        children[1],
+                        probability * right,
+                        origin,
+                        probabilities,
+                    );
+                }
+            }
+        }
+    }
+
+    fn all_selections() -> Vec<LightSelection> {
+        vec![
+            LightSelection::Uniform,
+            LightSelection::Power,
+            LightSelection::Tree,
+        ]
+    }
+
+    #[test]
+    fn picks_follow_probabilities() {
+        let mut rng = rand::thread_rng();
+        for selection in all_selections() {
+            let sampler = sampler(selection);
+            for origin in shading_points() {
+                let expected = light_probabilities(&sampler, &origin);
+                assert!((expected.iter().sum::<f32>() - 1.0).abs() < 1.0e-5);
+
+                let trials = 20000;
+                let mut counts = vec![0; expected.len()];
+                for _ in 0..trials {
+                    counts[sampler.pick(&origin, &mut rng).unwrap()] += 1;
+                }
+                for (count, p) in counts.iter().zip(&expected) {
+                    assert!((*count as f32 / trials as f32 - p).abs() < 0.015);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_is_probability_times_light_pdf() {
+        let mut rng = rand::thread_rng();
+        for selection in all_selections() {
+            let sampler = sampler(selection);
+            for origin in shading_points() {
+                let probabilities = light_probabilities(&sampler, &origin);
+                for _ in 0..200 {
+                    let direction = sampler.random(&origin, &mut rng).unwrap();
+                    let expected: f32 = sampler
+                        .lights
+                        .iter()
+                        .zip(&probabilities)
+                        .map(|(l, p)| p * l.pdf_value(&origin, &direction))
+                        .sum();
+                    let pdf = sampler.pdf_value(&origin, &direction);
+                    assert!((pdf - expected).abs() <= 1.0e-4 * expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_directions_cover_lights() {
+        // Weighting sampled directions by the uniform density of directions over their pdf
+        // estimates the fraction of the sphere the lights cover, known exactly for spheres
+        let mut rng = rand::thread_rng();
+        for selection in all_selections() {
+            let sampler = sampler(selection);
+            for origin in shading_points() {
+                let covered: f32 = spheres()
+                    .iter()
+                    .map(|(center, radius, _)| {
+                        let distance = (*center - origin).length();
+                        let cos_theta_max = (1.0 - radius * radius / (distance * distance)).sqrt();
+                        (1.0 - cos_theta_max) / 2.0
+                    })
+                    .sum();
+
+                let trials = 20000;
+                let mut sum = 0.0;
+                for _ in 0..trials {
+                    // Directions grazing a sphere can miss it, the renderer skips them too
+                    let direction = sampler.random(&origin, &mut rng).unwrap();
+                    let pdf = sampler.pdf_value(&origin, &direction);
+                    if pdf > 0.0 {
+                        sum += 1.0 / (4.0 * PI * pdf);
+                    }
+                }
+                let estimate = sum / trials as f32;
+                assert!((estimate / covered - 1.0).abs() < 0.05);
+            }
+        }
+    }
+}
@@ -1,107 +1,243 @@
+use std::env;
 use std::io;
-use std::rc::Rc;
-
-mod camera;
-mod color;
-mod hittable;
-//mod hittable_list;
-mod aabb;
-mod material;
-mod ray;
-mod sphere;
-mod triangle;
-mod utility;
-mod vec;
-
-use hittable::{HitRecord, Hittable};
-use material::*;
+use std::mem;
+
+mod scenes;
+
 use rand::prelude::*;
-use ray::Ray;
-use sphere::Sphere;
-use utility::*;
-//use triangle::Triangle;
-use vec::{Color, Point3, Vec3};
+use ray_tracer::color;
+use ray_tracer::hittable::{HitRecord, Hittable};
+use ray_tracer::light::Light;
+use ray_tracer::material;
+use ray_tracer::ray::Ray;
+use ray_tracer::utility::*;
+use ray_tracer::vec::{self, Color, Point3, Vec3};
+use scenes::Scene;
 
 const SAMPLES_PER_PIXEL: i32 = 100;
-const MAX_DEPTH: i32 = 50;
+// Bounces after which paths are ended at random by Russian roulette
+const ROULETTE_DEPTH: i32 = 3;
+// Highest chance of a path surviving the roulette, so even paths that lose no energy end
+const MAX_SURVIVAL: f32 = 0.95;
 
-fn ray_color(ray: &mut Ray, world: &dyn Hittable, rng: &mut ThreadRng, depth: i32) -> Color {
-    let mut record = HitRecord::empty();
+// Radiance along the ray, followed from bounce to bounce until it escapes, is absorbed or loses
+// the roulette
+fn ray_color(mut ray: Ray, scene: &Scene, rng: &mut ThreadRng) -> Color {
+    let mut color = Color::empty();
+    // Product of the BSDF weights along the path so far
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    // Density of the direction of the current ray, for weighting the lights it hits against
+    // light samples, none for camera rays and specular bounces
+    let mut bsdf_pdf: Option<f32> = None;
+    let mut depth = 0;
+
+    loop {
+        let mut record = HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        let hit = scene.world.hit(&ray, &mut t_min, &mut t_max, &mut record);
+
+        // Travel through the atmosphere up to the surface, possibly scattering on the way
+        let mut scattered_in_atmosphere = false;
+        if let Some(atmosphere) = &scene.atmosphere {
+            let ray_length = ray.direction().length();
+            let distance = if hit { record.t * ray_length } else { INFINITY };
+            let sample = atmosphere.sample(distance, rng);
+            throughput = throughput * sample.weight;
+            if let Some(scatter_distance) = sample.scatter_distance {
+                let p = ray.at(scatter_distance / ray_length);
+                let phase_pdf = 1.0 / (4.0 * PI);
+                let phase = |_: &Vec3| (Color::new(phase_pdf, phase_pdf, phase_pdf), phase_pdf);
+                color = color + throughput * direct_light(&ray, &p, scene, rng, phase);
+                ray = ray.spawn(p, vec::random_unit_vector(rng));
+                bsdf_pdf = Some(phase_pdf);
+                scattered_in_atmosphere = true;
+            }
+        }
+
+        if !scattered_in_atmosphere {
+            if !hit {
+                let direction = vec::unit_vector(ray.direction());
+                let mut background = scene.background.color(&direction);
+                for light in &scene.analytic_lights {
+                    let mut emitted = light.emitted(&direction);
+                    if let Some(bsdf_pdf) = bsdf_pdf {
+                        emitted = emitted
+                            * power_heuristic(bsdf_pdf, light.pdf(&ray.origin(), &direction));
+                    }
+                    background = background + emitted;
+                }
+                return color + throughput * background;
+            }
+
+            let material = record.material.clone();
+            // A ray reaching a back face crossed the inside of the object, whose absorption applies
+            // to the light found here as well as to the rest of the path
+            let absorption = material.interior_absorption();
+            throughput = throughput * material::interior_attenuation(&absorption, &ray, &record);
+            let mut emitted = material.emitted(record.u, record.v, &record.p);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if material.is_emissive() {
+                    let light_pdf = scene.lights.pdf_value(&ray.origin(), &ray.direction());
+                    emitted = emitted * power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
 
-    if depth <= 0 {
+            let wo = -vec::unit_vector(ray.direction());
+            let specular = material.is_specular_at(&record);
+            let direct = if specular {
+                Color::empty()
+            } else {
+                direct_light(&ray, &record.p, scene, rng, |wi| {
+                    (
+                        material.eval(&record, &wo, wi),
+                        material.pdf(&record, &wo, wi),
+                    )
+                })
+            };
+            color = color + throughput * (emitted + direct);
+
+            let sample = match material.sample(&mut ray, &record, rng) {
+                Some(sample) => sample,
+                None => return color,
+            };
+            bsdf_pdf = if specular || sample.is_specular() {
+                None
+            } else {
+                Some(sample.pdf)
+            };
+            throughput = throughput * sample.weight;
+            ray = ray.spawn(record.p, sample.direction);
+        }
+
+        depth += 1;
+        throughput = match roulette(throughput, depth, rng) {
+            Some(throughput) => throughput,
+            None => return color,
+        };
+    }
+}
+
+// Past a few bounces, ends paths carrying little light with a high chance and makes up for it in
+// the ones that survive, which keeps the estimate unbiased. Gives the throughput of a surviving
+// path, none for one that ends.
+fn roulette(throughput: Color, depth: i32, rng: &mut ThreadRng) -> Option<Color> {
+    if depth < ROULETTE_DEPTH {
+        return Some(throughput);
+    }
+    // Checked per channel, as max and min skip a NaN and would let it through
+    if throughput.x().is_nan() || throughput.y().is_nan() || throughput.z().is_nan() {
+        return None;
+    }
+    let survival = throughput
+        .x()
+        .max(throughput.y())
+        .max(throughput.z())
+        .min(MAX_SURVIVAL);
+    if random_double(rng) >= survival {
+        return None;
+    }
+    Some(throughput / survival)
+}
+
+// Light reaching `p` straight from the lights. `f` gives the BSDF times the cosine, or the phase
+// function, for a direction towards a light along with the density of sampling that direction by
+// scattering, which light samples are weighted against.
+fn direct_light<F: Fn(&Vec3) -> (Color, f32)>(
+    ray: &Ray,
+    p: &Point3,
+    scene: &Scene,
+    rng: &mut ThreadRng,
+    f: F,
+) -> Color {
+    let mut direct = area_light(ray, p, scene, rng, &f);
+    for light in &scene.analytic_lights {
+        direct = direct + analytic_light(light.as_ref(), ray, p, scene, rng, &f);
+    }
+    direct
+}
+
+// Light from a sampled point of the emissive objects
+fn area_light<F: Fn(&Vec3) -> (Color, f32)>(
+    ray: &Ray,
+    p: &Point3,
+    scene: &Scene,
+    rng: &mut ThreadRng,
+    f: &F,
+) -> Color {
+    if scene.lights.is_empty() {
         return Color::empty();
     }
 
-    if world.hit(ray, &mut 0.001, &mut INFINITY, &mut record) {
-        let mut scattered = Ray::empty();
-        let mut attenuation = Color::empty();
-        if record
-            .material
-            .scatter(ray, &record, &mut attenuation, &mut scattered, rng)
-        {
-            return attenuation * ray_color(&mut scattered, world, rng, depth - 1);
-        }
+    let direction = match scene.lights.random(p, rng) {
+        Some(direction) => vec::unit_vector(direction),
+        None => return Color::empty(),
+    };
+    let pdf = scene.lights.pdf_value(p, &direction);
+    if pdf <= 0.0 {
+        return Color::empty();
+    }
+
+    // The closest hit along the shadow ray tells both whether a light is visible and its radiance
+    let shadow = ray.spawn(*p, direction);
+    let mut record = HitRecord::empty();
+    let (mut t_min, mut t_max) = (0.001, INFINITY);
+    // Other emitters are left to be found by scattering
+    if !scene
+        .world
+        .hit(&shadow, &mut t_min, &mut t_max, &mut record)
+        || !record.material.is_emissive()
+    {
         return Color::empty();
     }
-    let unit_direction = vec::unit_vector(ray.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+    let mut emitted = record.material.emitted(record.u, record.v, &record.p);
+    if let Some(atmosphere) = &scene.atmosphere {
+        emitted = emitted * atmosphere.transmittance(record.t);
+    }
+    let (value, bsdf_pdf) = f(&direction);
+    value * emitted * power_heuristic(pdf, bsdf_pdf) / pdf
 }
 
-fn random_scene(rng: &mut ThreadRng) -> hittable::HittableList {
-    let mut world = hittable::HittableList::new();
-    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        material_ground.clone(),
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_double(rng);
-            let center = Point3::new(
-                a as f32 + 0.9 * random_double(rng),
-                0.2,
-                b as f32 + 0.9 * random_double(rng),
-            );
-
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let material: Rc<dyn material::Material>;
-                if choose_mat < 0.8 {
-                    let albedo = Color::random(rng) * Color::random(rng);
-                    material = Rc::new(Lambertian::new(albedo));
-                } else if choose_mat < 0.95 {
-                    let albedo = Color::random_range(rng, 0.5, 1.0);
-                    let fuzz = random_double_range(rng, 0.0, 0.5);
-                    material = Rc::new(Metal::new(albedo, fuzz));
-                } else {
-                    material = Rc::new(Dielectric::new(1.5));
-                }
-                world.add(Box::new(Sphere::new(center, 0.2, material.clone())));
-            }
-        }
+// Light from one of the analytic lights, unless a shadow ray finds it blocked
+fn analytic_light<F: Fn(&Vec3) -> (Color, f32)>(
+    light: &dyn Light,
+    ray: &Ray,
+    p: &Point3,
+    scene: &Scene,
+    rng: &mut ThreadRng,
+    f: &F,
+) -> Color {
+    let sample = match light.sample(p, rng) {
+        Some(sample) => sample,
+        None => return Color::empty(),
+    };
+
+    let shadow = ray.spawn(*p, sample.direction);
+    let visibility = scene.world.transmittance(&shadow, 0.001, sample.distance);
+    if visibility <= 0.0 {
+        return Color::empty();
     }
+    let mut radiance = sample.radiance * visibility;
+    if let Some(atmosphere) = &scene.atmosphere {
+        // Lights at an infinite distance still shine through channels without extinction, as
+        // they do for rays escaping the scene
+        radiance = radiance * atmosphere.transmittance(sample.distance);
+    }
+
+    let (value, bsdf_pdf) = f(&sample.direction);
+    match sample.pdf {
+        Some(pdf) => value * radiance * power_heuristic(pdf, bsdf_pdf) / pdf,
+        None => value * radiance,
+    }
+}
 
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material1.clone(),
-    )));
-    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Box::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2.clone(),
-    )));
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Box::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material3.clone(),
-    )));
-    world
+// Weight of a sample from a strategy with density `pdf` when `other` could also have produced it
+fn power_heuristic(pdf: f32, other: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
 }
 
 fn main() {
@@ -117,69 +253,41 @@ fn main() {
 
     // World
 
-    let mut world = random_scene(&mut rng);
-
-    // let material_ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    // let material_center = Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    // //let material_left = Rc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.3));
-    // //let material_center = Rc::new(Dielectric::new(1.5));
-    // let material_left = Rc::new(Dielectric::new(1.5));
-    // let material_right = Rc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
-
-    // let mut world = hittable_list::HittableList::new();
-
-    // world.add(Box::new(Sphere::new(
-    //     Point3::new(0.0, -100.5, -1.0),
-    //     100.0,
-    //     material_ground.clone(),
-    // )));
-    // world.add(Box::new(Sphere::new(
-    //     Point3::new(0.0, 0.0, -1.0),
-    //     0.5,
-    //     material_center.clone(),
-    // )));
-    // world.add(Box::new(Sphere::new(
-    //     Point3::new(1.0, 0.0, -1.0),
-    //     0.5,
-    //     material_right.clone(),
-    // )));
-    // world.add(Box::new(Sphere::new(
-    //     Point3::new(-1.0, 0.0, -1.0),
-    //     0.5,
-    //     material_left.clone(),
-    // )));
-    // world.add(Box::new(Sphere::new(
-    //     Point3::new(-1.0, 0.0, -1.0),
-    //     -0.45,
-    //     material_left.clone(),
-    // )));
-
-    // world.add(Box::new(Triangle::new(
-    //     [
-    //         Point3::new(0.0, 1.5, -1.0),
-    //         Point3::new(0.5, -1.5, -1.0),
-    //         Point3::new(-0.5, -1.5, -1.0),
-    //     ],
-    //     material_right.clone(),
-    // )));
-
-    // Camera
-
-    let look_from = Point3::new(13.0, 2.0, 3.0);
-    let look_at = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let camera = camera::Camera::new(
-        look_from,
-        look_at,
-        vup,
-        20.0,
-        ascpect_ratio,
-        aperture,
-        dist_to_focus,
-    );
+    let mut scene = match env::args().nth(1).as_deref() {
+        Some("boxes") => scenes::boxes(ascpect_ratio),
+        Some("quads") => scenes::quads(ascpect_ratio),
+        Some("shapes") => scenes::shapes(ascpect_ratio),
+        Some("motion") => scenes::motion_blur(&mut rng, ascpect_ratio),
+        Some("volumes") => scenes::volumes(ascpect_ratio),
+        Some("cloud") => scenes::cloud(ascpect_ratio, env::args().nth(2)),
+        Some("haze") => scenes::haze(&mut rng, ascpect_ratio),
+        Some("metals") => scenes::metals(ascpect_ratio),
+        Some("glass") => scenes::frosted_glass(ascpect_ratio),
+        Some("tinted") => scenes::tinted_glass(ascpect_ratio),
+        Some("dispersion") => scenes::dispersion(ascpect_ratio),
+        Some("principled") => scenes::principled(ascpect_ratio),
+        Some("coated") => scenes::coated(ascpect_ratio),
+        Some("clay") => scenes::rough_diffuse(ascpect_ratio),
+        Some("bumps") => scenes::bumps(ascpect_ratio, env::args().nth(2)),
+        Some("cutouts") => scenes::cutouts(&mut rng, ascpect_ratio),
+        Some("sheets") => scenes::sheets(ascpect_ratio),
+        Some("mis") => scenes::glossy_plates(ascpect_ratio),
+        Some("lamps") => scenes::lamps(ascpect_ratio),
+        Some("many") => scenes::many_lights(&mut rng, ascpect_ratio),
+        Some("sky") => {
+            let elevation = env::args().nth(2).and_then(|a| a.parse().ok());
+            let turbidity = env::args().nth(3).and_then(|a| a.parse().ok());
+            scenes::daylight(
+                ascpect_ratio,
+                elevation.unwrap_or(35.0),
+                turbidity.unwrap_or(3.0),
+            )
+        }
+        _ => scenes::random_scene(&mut rng, ascpect_ratio),
+    };
+    scene.find_lights();
+    let (time0, time1) = scene.camera.shutter();
+    scene.world = mem::take(&mut scene.world).into_bvh(time0, time1);
 
     // Renderer
 
@@ -194,11 +302,280 @@ fn main() {
             for _ in 0..SAMPLES_PER_PIXEL {
                 let u = (i as f32 + random_double(&mut rng)) / (image_width - 1) as f32;
                 let v = (j as f32 + random_double(&mut rng)) / (image_height - 1) as f32;
-                let mut ray = camera.get_ray(u, v, &mut rng);
-                pixel_color = pixel_color + ray_color(&mut ray, &mut world, &mut rng, MAX_DEPTH);
+                let ray = scene.camera.get_ray(u, v, &mut rng);
+                pixel_color = pixel_color + ray_color(ray, &scene, &mut rng);
             }
             color::write_color(&mut handle, pixel_color, SAMPLES_PER_PIXEL);
         }
     }
     eprint!("\nDone.\n");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ray_tracer::atmosphere::Atmosphere;
+    use ray_tracer::box_shape::BoxShape;
+    use ray_tracer::camera::Camera;
+    use ray_tracer::hittable::HittableList;
+    use ray_tracer::light::DirectionalLight;
+    use ray_tracer::quad::Quad;
+    use ray_tracer::sphere::Sphere;
+    use scenes::Background;
+    use std::rc::Rc;
+
+    // Slab of absorbing rough glass under a small area light, so that light samples carry most
+    // of the light reaching through the top of the slab
+    fn absorbing_slab() -> Scene {
+        let mut world = HittableList::new();
+        world.add(Box::new(BoxShape::new(
+            Point3::new(-5.0, -1.0, -5.0),
+            Point3::new(5.0, 0.0, 5.0),
+            Rc::new(material::RoughDielectric::with_absorption(
+                1.5,
+                1.0,
+                Color::new(2.0, 2.0, 2.0),
+            )),
+        )));
+        world.add(Box::new(Quad::new(
+            Point3::new(-0.5, 1.0, -0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Rc::new(material::DiffuseLight::new(Color::new(16.0, 16.0, 16.0))),
+        )));
+
+        let mut scene = Scene::new(world, camera());
+        scene.background = Background::Solid(Color::empty());
+        scene
+    }
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            5.0,
+        )
+    }
+
+    fn mean_radiance(scene: &Scene, rng: &mut ThreadRng) -> f32 {
+        // From inside of the slab towards its top face and the light above
+        let trials = 100000;
+        let mut sum = 0.0;
+        for _ in 0..trials {
+            let ray = Ray::new(Point3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+            sum += ray_color(ray, scene, rng).y();
+        }
+        sum / trials as f32
+    }
+
+    #[test]
+    fn light_samples_see_interior_absorption() {
+        let mut rng = rand::thread_rng();
+
+        // Without lights to sample, all light is found by following the BSDF
+        let scene = absorbing_slab();
+        let bsdf_only = mean_radiance(&scene, &mut rng);
+
+        let mut scene = absorbing_slab();
+        scene.find_lights();
+        let with_light_samples = mean_radiance(&scene, &mut rng);
+
+        assert!(bsdf_only > 0.0);
+        assert!((with_light_samples / bsdf_only - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn infinite_lights_shine_through_clear_atmosphere() {
+        let mut rng = rand::thread_rng();
+        let mut scene = Scene::new(HittableList::new(), camera());
+        // Only the red channel is clear
+        scene.atmosphere = Some(Atmosphere::new(Color::empty(), Color::new(0.0, 0.1, 0.1)));
+        let sun = DirectionalLight::new(Vec3::new(0.0, 1.0, 0.0), Color::new(1.0, 1.0, 1.0), 0.5);
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let p = Point3::new(0.0, 0.0, 0.0);
+        let f = |_: &Vec3| (Color::new(1.0, 1.0, 1.0), 0.0);
+        for _ in 0..100 {
+            let direct = analytic_light(&sun, &ray, &p, &scene, &mut rng, &f);
+            assert!(direct.x() > 0.0);
+            assert_eq!(direct.y(), 0.0);
+            assert_eq!(direct.z(), 0.0);
+        }
+    }
+
+    #[test]
+    fn roulette_keeps_the_expected_throughput() {
+        let mut rng = rand::thread_rng();
+        let cases = [
+            Color::new(0.3, 0.2, 0.1),
+            Color::new(0.02, 0.0, 0.01),
+            // Above the highest survival chance, as after a bright bounce
+            Color::new(2.0, 1.0, 0.5),
+        ];
+        for throughput in cases.iter() {
+            // Paths aren't ended before the roulette depth
+            let kept = roulette(*throughput, ROULETTE_DEPTH - 1, &mut rng).unwrap();
+            assert_eq!(kept.x(), throughput.x());
+
+            let trials = 200000;
+            let mut sum = Color::empty();
+            for _ in 0..trials {
+                if let Some(survivor) = roulette(*throughput, ROULETTE_DEPTH, &mut rng) {
+                    sum = sum + survivor;
+                }
+            }
+            let mean = sum / trials as f32;
+            for c in 0..3 {
+                assert!((mean[c] - throughput[c]).abs() <= 0.06 * throughput[c]);
+            }
+        }
+
+        for nan in [
+            Color::new(f32::NAN, 0.5, 0.5),
+            Color::new(f32::NAN, f32::NAN, f32::NAN),
+        ]
+        .iter()
+        {
+            assert!(roulette(*nan, ROULETTE_DEPTH, &mut rng).is_none());
+        }
+    }
+
+    #[test]
+    fn lossless_paths_end() {
+        // Inside a perfect mirror nothing is lost, only the roulette ends the path
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            10.0,
+            Rc::new(material::Metal::new(Color::new(1.0, 1.0, 1.0), 0.0)),
+        )));
+        let scene = Scene::new(world, camera());
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let direction = vec::random_unit_vector(&mut rng);
+            let ray = Ray::new(Point3::new(1.0, 2.0, 3.0), direction, 0.0);
+            let color = ray_color(ray, &scene, &mut rng);
+            assert_eq!(color.x(), 0.0);
+        }
+    }
+
+    // Floor under a square light of half width `size` one unit above its middle
+    fn lit_floor(floor: Rc<dyn material::Material>, size: f32) -> Scene {
+        let mut world = HittableList::new();
+        world.add(Box::new(Quad::new(
+            Point3::new(-50.0, 0.0, -50.0),
+            Vec3::new(100.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 100.0),
+            floor,
+        )));
+        world.add(Box::new(Quad::new(
+            Point3::new(-size, 1.0, -size),
+            Vec3::new(2.0 * size, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0 * size),
+            Rc::new(material::DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        )));
+
+        let mut scene = Scene::new(world, camera());
+        scene.background = Background::Solid(Color::empty());
+        scene
+    }
+
+    // Mean and variance of the radiance leaving the middle of the floor straight up
+    fn floor_radiance(scene: &Scene, trials: i32, rng: &mut ThreadRng) -> (f32, f32) {
+        let (mut sum, mut sum2) = (0.0, 0.0);
+        for _ in 0..trials {
+            let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+            let radiance = ray_color(ray, scene, rng).y();
+            sum += radiance;
+            sum2 += radiance * radiance;
+        }
+        let mean = sum / trials as f32;
+        (mean, sum2 / trials as f32 - mean * mean)
+    }
+
+    // Radiance reflected by a Lambertian floor from the light of `lit_floor`, from the form
+    // factor of a point towards the four rectangles the light splits into above it
+    fn lit_floor_radiance(albedo: f32, size: f32) -> f32 {
+        let a = size / (1.0 + size * size).sqrt();
+        let corner = 2.0 * a * a.atan() / (2.0 * PI);
+        albedo * 4.0 * 4.0 * corner
+    }
+
+    #[test]
+    fn light_samples_find_small_lights() {
+        let mut rng = rand::thread_rng();
+        let size = 0.1;
+        let expected = lit_floor_radiance(0.5, size);
+        let floor = || -> Rc<dyn material::Material> {
+            Rc::new(material::Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+        };
+
+        let mut scene = lit_floor(floor(), size);
+        scene.find_lights();
+        let (mean, variance) = floor_radiance(&scene, 20000, &mut rng);
+        assert!((mean / expected - 1.0).abs() < 0.02);
+
+        // Bounces alone rarely hit a light this small
+        let scene = lit_floor(floor(), size);
+        let (_, bsdf_only_variance) = floor_radiance(&scene, 20000, &mut rng);
+        assert!(variance * 100.0 < bsdf_only_variance);
+    }
+
+    #[test]
+    fn power_heuristic_weights_add_up() {
+        for (a, b) in [(1.0f32, 1.0), (0.3, 2.0), (5.0, 0.0), (1e-3, 1e3)].iter() {
+            let sum = power_heuristic(*a, *b) + power_heuristic(*b, *a);
+            assert!((sum - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn weighted_strategies_agree_with_bounces() {
+        let mut rng = rand::thread_rng();
+        let size = 1.0;
+        let floors: [(Rc<dyn material::Material>, Option<f32>); 2] = [
+            (
+                Rc::new(material::Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+                Some(lit_floor_radiance(0.5, size)),
+            ),
+            // Glossy, where both strategies find much of the light
+            (Rc::new(material::Conductor::aluminium(0.3)), None),
+        ];
+        for (floor, expected) in floors.iter() {
+            let scene = lit_floor(floor.clone(), size);
+            let (bsdf_only, _) = floor_radiance(&scene, 100000, &mut rng);
+
+            let mut scene = lit_floor(floor.clone(), size);
+            scene.find_lights();
+            let (weighted, _) = floor_radiance(&scene, 100000, &mut rng);
+
+            assert!(bsdf_only > 0.0);
+            assert!((weighted / bsdf_only - 1.0).abs() < 0.03);
+            if let Some(expected) = expected {
+                assert!((weighted / expected - 1.0).abs() < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn lights_seen_in_mirrors_keep_their_weight() {
+        // Specular bounces aren't weighted against light samples, which can't find their
+        // direction, so the reflected light comes out whole either way
+        let mut rng = rand::thread_rng();
+        let mirror = || -> Rc<dyn material::Material> {
+            Rc::new(material::Metal::new(Color::new(1.0, 1.0, 1.0), 0.0))
+        };
+        let scene = lit_floor(mirror(), 1.0);
+        assert_eq!(floor_radiance(&scene, 100, &mut rng), (4.0, 0.0));
+
+        let mut scene = lit_floor(mirror(), 1.0);
+        scene.find_lights();
+        assert_eq!(floor_radiance(&scene, 100, &mut rng), (4.0, 0.0));
+    }
+}
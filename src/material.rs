@@ -1,15 +1,125 @@
-use crate::{hittable, ray, utility::*, vec};
+use crate::{hittable, microfacet, onb, ray, spectrum, texture, utility::*, vec};
 use rand::prelude::*;
+use std::ops::BitOr;
+use std::rc::Rc;
+
+// Kinds of scattering a sample comes from, combined with `|`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Lobe(u8);
+
+impl Lobe {
+    pub const REFLECTION: Lobe = Lobe(1);
+    pub const TRANSMISSION: Lobe = Lobe(2);
+    pub const DIFFUSE: Lobe = Lobe(4);
+    pub const GLOSSY: Lobe = Lobe(8);
+    pub const SPECULAR: Lobe = Lobe(16);
+
+    pub fn contains(self, other: Lobe) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Lobe {
+    type Output = Lobe;
+
+    fn bitor(self, other: Lobe) -> Lobe {
+        Lobe(self.0 | other.0)
+    }
+}
+
+// Direction sampled by a material for the light arriving at a hit point
+pub struct BsdfSample {
+    // Unit direction pointing away from the hit point
+    pub direction: vec::Vec3,
+    // BSDF times the cosine of the direction over its pdf
+    pub weight: vec::Color,
+    // Density of the direction. Specular lobes, and materials that are specular as a whole, only
+    // give the probability of the choices made to sample it.
+    pub pdf: f32,
+    pub lobe: Lobe,
+}
+
+impl BsdfSample {
+    // Sample of `wi` weighted by what the material's `eval` and `pdf` give for it
+    pub fn evaluated<M: Material + ?Sized>(
+        material: &M,
+        record: &hittable::HitRecord,
+        wo: &vec::Vec3,
+        wi: vec::Vec3,
+        lobe: Lobe,
+    ) -> Option<BsdfSample> {
+        let pdf = material.pdf(record, wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: wi,
+            weight: material.eval(record, wo, &wi) / pdf,
+            pdf,
+            lobe,
+        })
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.lobe.contains(Lobe::SPECULAR)
+    }
+}
 
 pub trait Material {
-    fn scatter(
+    // Picks the direction the light scattered along `ray` comes from, nothing if it's absorbed.
+    // Dispersive materials may assign a wavelength to the ray.
+    fn sample(
         &self,
         ray: &mut ray::Ray,
         record: &hittable::HitRecord,
-        attenuation: &mut vec::Color,
-        scattered: &mut ray::Ray,
         rng: &mut ThreadRng,
-    ) -> bool;
+    ) -> Option<BsdfSample>;
+
+    // Radiance emitted at the hit point, nothing for materials that aren't light sources
+    fn emitted(&self, _u: f32, _v: f32, _p: &vec::Point3) -> vec::Color {
+        vec::Color::empty()
+    }
+
+    // Probability that a ray hitting the surface at this point stops there instead of passing
+    // through a cutout
+    fn opacity(&self, _u: f32, _v: f32, _p: &vec::Point3) -> f32 {
+        1.0
+    }
+
+    // Whether objects made of it are light sources to be sampled directly
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Absorption coefficient per unit of distance inside closed objects made of it. Paths that
+    // reach a back face are attenuated by the renderer for the distance they traveled inside.
+    fn interior_absorption(&self) -> vec::Color {
+        vec::Color::empty()
+    }
+
+    // Specular materials, like mirrors and smooth glass, sample directions that can't be
+    // evaluated. The others implement `eval` and `pdf`, and get part of their direct lighting
+    // from shadow rays towards the lights.
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    // Whether the material is specular where `record` hit it, for materials that differ across
+    // the surface
+    fn is_specular_at(&self, _record: &hittable::HitRecord) -> bool {
+        self.is_specular()
+    }
+
+    // BSDF times the cosine of `wi`, for unit directions `wo` and `wi` pointing away from the hit
+    // point
+    fn eval(&self, _record: &hittable::HitRecord, _wo: &vec::Vec3, _wi: &vec::Vec3) -> vec::Color {
+        vec::Color::empty()
+    }
+
+    // Density of `sample` picking `wi`
+    fn pdf(&self, _record: &hittable::HitRecord, _wo: &vec::Vec3, _wi: &vec::Vec3) -> f32 {
+        0.0
+    }
 }
 
 pub struct Lambertian {
@@ -23,18 +133,241 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(
+    fn sample(
         &self,
-        _ray: &mut ray::Ray,
+        ray: &mut ray::Ray,
         record: &hittable::HitRecord,
-        attenuation: &mut vec::Color,
-        scattered: &mut ray::Ray,
         rng: &mut ThreadRng,
-    ) -> bool {
-        let scatter_direction = record.normal + vec::random_unit_vector(rng);
-        *scattered = ray::Ray::new(record.p, scatter_direction);
-        *attenuation = self.albedo;
-        true
+    ) -> Option<BsdfSample> {
+        let wo = -vec::unit_vector(ray.direction());
+        let wi = onb::Onb::build_from_w(&record.normal).local(&vec::random_cosine_direction(rng));
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::DIFFUSE | Lobe::REFLECTION)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, _wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        self.albedo * (vec::dot(&record.normal, wi).max(0.0) / PI)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, _wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        vec::dot(&record.normal, wi).max(0.0) / PI
+    }
+}
+
+// Rough diffuse surface made of V-shaped Lambertian facets, after Oren and Nayar 1994 in its
+// qualitative form. `sigma` is the standard deviation of the facet angle in radians, zero gives
+// back a Lambertian surface.
+pub struct OrenNayar {
+    pub albedo: vec::Color,
+    a: f32,
+    b: f32,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: vec::Color, sigma: f32) -> OrenNayar {
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - 0.5 * sigma2 / (sigma2 + 0.33),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl Material for OrenNayar {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let wo = -vec::unit_vector(ray.direction());
+        let wi = frame.local(&vec::random_cosine_direction(rng));
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::DIFFUSE | Lobe::REFLECTION)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return vec::Color::empty();
+        }
+
+        // Cosine of the azimuth between the directions, from their projections on the surface
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x() * wi.x() + wo.y() * wi.y()) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // sin(alpha) tan(beta) with alpha the larger of the two angles and beta the smaller
+        let (sin_alpha, tan_beta) = if wi.z() < wo.z() {
+            (sin_i, sin_o / wo.z())
+        } else {
+            (sin_o, sin_i / wi.z())
+        };
+        self.albedo * ((self.a + self.b * cos_phi * sin_alpha * tan_beta) * wi.z() / PI)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, _wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        vec::dot(&record.normal, wi).max(0.0) / PI
+    }
+}
+
+// Thin sheet like a leaf, paper or a curtain, diffusely reflecting part of the light and diffusely
+// transmitting part of it to the other side. Being thin, light leaves from the hit point without
+// any refraction offset.
+pub struct Translucent {
+    pub reflectance: vec::Color,
+    pub transmittance: vec::Color,
+}
+
+impl Translucent {
+    pub fn new(reflectance: vec::Color, transmittance: vec::Color) -> Translucent {
+        Translucent {
+            reflectance,
+            transmittance,
+        }
+    }
+
+    // Probability of sampling the reflection, in proportion to its share of the scattered light
+    fn reflection_probability(&self) -> f32 {
+        let r = self.reflectance.x() + self.reflectance.y() + self.reflectance.z();
+        let t = self.transmittance.x() + self.transmittance.y() + self.transmittance.z();
+        if r + t > 0.0 {
+            r / (r + t)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Material for Translucent {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let (side, lobe) = if random_double(rng) < self.reflection_probability() {
+            (record.normal, Lobe::REFLECTION)
+        } else {
+            (-record.normal, Lobe::TRANSMISSION)
+        };
+        let wi = onb::Onb::build_from_w(&side).local(&vec::random_cosine_direction(rng));
+
+        let wo = -vec::unit_vector(ray.direction());
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::DIFFUSE | lobe)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // Light on either side of the surface is reflected or transmitted
+    fn eval(&self, record: &hittable::HitRecord, _wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let cos = vec::dot(&record.normal, wi);
+        if cos >= 0.0 {
+            self.reflectance * (cos / PI)
+        } else {
+            self.transmittance * (-cos / PI)
+        }
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, _wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        let cos = vec::dot(&record.normal, wi);
+        let p = self.reflection_probability();
+        if cos >= 0.0 {
+            p * cos / PI
+        } else {
+            (1.0 - p) * -cos / PI
+        }
+    }
+}
+
+// Different materials on the front and the back of a surface, as told by the side of the
+// geometric normal the ray comes from. Emission and opacity don't know the side and come from the
+// front material.
+pub struct TwoSided {
+    pub front: Rc<dyn Material>,
+    pub back: Rc<dyn Material>,
+}
+
+impl TwoSided {
+    pub fn new(front: Rc<dyn Material>, back: Rc<dyn Material>) -> TwoSided {
+        TwoSided { front, back }
+    }
+}
+
+impl Material for TwoSided {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let side = if record.front_face {
+            &self.front
+        } else {
+            &self.back
+        };
+        side.sample(ray, record, rng)
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &vec::Point3) -> vec::Color {
+        self.front.emitted(u, v, p)
+    }
+
+    fn opacity(&self, u: f32, v: f32, p: &vec::Point3) -> f32 {
+        self.front.opacity(u, v, p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.front.is_emissive()
+    }
+
+    // The inside of a closed object is behind its back faces
+    fn interior_absorption(&self) -> vec::Color {
+        self.back.interior_absorption()
+    }
+
+    // Without a hit to tell the side, either side being specular makes the whole specular
+    fn is_specular(&self) -> bool {
+        self.front.is_specular() || self.back.is_specular()
+    }
+
+    fn is_specular_at(&self, record: &hittable::HitRecord) -> bool {
+        if record.front_face {
+            self.front.is_specular_at(record)
+        } else {
+            self.back.is_specular_at(record)
+        }
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        if record.front_face {
+            self.front.eval(record, wo, wi)
+        } else {
+            self.back.eval(record, wo, wi)
+        }
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        if record.front_face {
+            self.front.pdf(record, wo, wi)
+        } else {
+            self.back.pdf(record, wo, wi)
+        }
     }
 }
 
@@ -51,73 +384,1211 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(
+    fn sample(
         &self,
         ray: &mut ray::Ray,
         record: &hittable::HitRecord,
-        attenuation: &mut vec::Color,
-        scattered: &mut ray::Ray,
         rng: &mut ThreadRng,
-    ) -> bool {
+    ) -> Option<BsdfSample> {
         let reflected = vec::reflect(&ray.direction(), &record.normal);
-        *scattered = ray::Ray::new(
-            record.p,
-            reflected + vec::random_in_unit_sphere(rng) * self.fuzz,
-        );
-        *attenuation = self.albedo;
-        vec::dot(&scattered.direction(), &record.normal) > 0.0
+        let direction = reflected + vec::random_in_unit_sphere(rng) * self.fuzz;
+        if vec::dot(&direction, &record.normal) <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: vec::unit_vector(direction),
+            weight: self.albedo,
+            pdf: 1.0,
+            lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+        })
+    }
+}
+
+// Rough metal with a GGX microfacet distribution and the Fresnel reflectance of a complex index
+// of refraction eta + i k, given per color channel
+pub struct Conductor {
+    pub eta: vec::Color,
+    pub k: vec::Color,
+    pub alpha: f32,
+}
+
+impl Conductor {
+    pub fn new(eta: vec::Color, k: vec::Color, roughness: f32) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            alpha: microfacet::roughness_to_alpha(roughness),
+        }
+    }
+
+    pub fn gold(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(0.143, 0.374, 1.442),
+            vec::Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(0.155, 0.117, 0.138),
+            vec::Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(0.200, 0.924, 1.102),
+            vec::Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(1.657, 0.880, 0.521),
+            vec::Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn chromium(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(3.105, 3.182, 2.323),
+            vec::Color::new(3.310, 3.330, 3.136),
+            roughness,
+        )
+    }
+
+    pub fn iron(roughness: f32) -> Conductor {
+        Conductor::new(
+            vec::Color::new(2.869, 2.945, 2.650),
+            vec::Color::new(3.077, 2.932, 2.808),
+            roughness,
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let wo = -vec::unit_vector(ray.direction());
+        let h = frame.local(&microfacet::sample_visible_normal(
+            &frame.to_local(&wo),
+            self.alpha,
+            rng,
+        ));
+        let wi = vec::reflect(&-wo, &h);
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::GLOSSY | Lobe::REFLECTION)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return vec::Color::empty();
+        }
+
+        let h = vec::unit_vector(wo + wi);
+        let fresnel = microfacet::fresnel_conductor(vec::dot(&wi, &h), &self.eta, &self.k);
+        fresnel
+            * (microfacet::d(&h, self.alpha) * microfacet::g2(&wo, &wi, self.alpha)
+                / (4.0 * wo.z()))
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let h = vec::unit_vector(wo + wi);
+        microfacet::visible_normal_pdf(&wo, &h, self.alpha) / (4.0 * vec::dot(&wo, &h))
     }
 }
 
 pub struct Dielectric {
     pub reflection_index: f32,
+    // Absorption coefficient of the interior per unit of distance
+    pub absorption: vec::Color,
+    // Wavelength dependence of the index, which then splits paths into single wavelengths
+    pub dispersion: Option<spectrum::Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(reflection_index: f32) -> Dielectric {
-        Dielectric { reflection_index }
+        Dielectric::with_absorption(reflection_index, vec::Color::empty())
+    }
+
+    pub fn with_absorption(reflection_index: f32, absorption: vec::Color) -> Dielectric {
+        Dielectric {
+            reflection_index,
+            absorption,
+            dispersion: None,
+        }
+    }
+
+    pub fn dispersive(dispersion: spectrum::Dispersion) -> Dielectric {
+        Dielectric {
+            reflection_index: dispersion.nominal_index(),
+            absorption: vec::Color::empty(),
+            dispersion: Some(dispersion),
+        }
+    }
+
+    // Index of refraction seen by the ray. A dispersive material assigns a wavelength to rays
+    // without one, in which case `weight` is multiplied by its color.
+    fn index_for(&self, ray: &mut ray::Ray, weight: &mut vec::Color, rng: &mut ThreadRng) -> f32 {
+        let dispersion = match self.dispersion {
+            Some(dispersion) => dispersion,
+            None => return self.reflection_index,
+        };
+
+        let wavelength = match ray.wavelength() {
+            Some(wavelength) => wavelength,
+            None => {
+                let wavelength = spectrum::sample_wavelength(rng);
+                ray.set_wavelength(wavelength);
+                *weight = *weight * spectrum::wavelength_weight(wavelength);
+                wavelength
+            }
+        };
+        dispersion.index(wavelength)
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(
+    fn sample(
         &self,
         ray: &mut ray::Ray,
         record: &hittable::HitRecord,
-        attenuation: &mut vec::Color,
-        scattered: &mut ray::Ray,
         rng: &mut ThreadRng,
-    ) -> bool {
-        *attenuation = vec::Color::new(1.0, 1.0, 1.0);
+    ) -> Option<BsdfSample> {
+        let mut weight = vec::Color::new(1.0, 1.0, 1.0);
+        let reflection_index = self.index_for(ray, &mut weight, rng);
         let etai_over_etat = if record.front_face {
-            1.0 / self.reflection_index
+            1.0 / reflection_index
         } else {
-            self.reflection_index
+            reflection_index
         };
         let unit_direction = vec::unit_vector(ray.direction());
 
         let cos_theta = vec::dot(&-unit_direction, &record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         if etai_over_etat * sin_theta > 1.0 {
-            let reflected = vec::reflect(&unit_direction, &record.normal);
-            *scattered = ray::Ray::new(record.p, reflected);
-            return true;
+            return Some(BsdfSample {
+                direction: vec::reflect(&unit_direction, &record.normal),
+                weight,
+                pdf: 1.0,
+                lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+            });
         }
 
         let reflect_prob = schlick(cos_theta, etai_over_etat);
         if random_double(rng) < reflect_prob {
-            let reflected = vec::reflect(&unit_direction, &record.normal);
-            *scattered = ray::Ray::new(record.p, reflected);
-            return true;
+            return Some(BsdfSample {
+                direction: vec::reflect(&unit_direction, &record.normal),
+                weight,
+                pdf: reflect_prob,
+                lobe: Lobe::SPECULAR | Lobe::REFLECTION,
+            });
         }
 
         let refracted = vec::refract(&unit_direction, &record.normal, etai_over_etat);
-        *scattered = ray::Ray::new(record.p, refracted);
+        Some(BsdfSample {
+            direction: vec::unit_vector(refracted),
+            weight,
+            pdf: 1.0 - reflect_prob,
+            lobe: Lobe::SPECULAR | Lobe::TRANSMISSION,
+        })
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.absorption
+    }
+}
+
+// Emits light without scattering any
+pub struct DiffuseLight {
+    pub emit: vec::Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: vec::Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(
+        &self,
+        _ray: &mut ray::Ray,
+        _record: &hittable::HitRecord,
+        _rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        None
+    }
+
+    fn emitted(&self, _u: f32, _v: f32, _p: &vec::Point3) -> vec::Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
         true
     }
 }
 
+// Scatters uniformly in all directions, used as the phase function of volumes
+pub struct Isotropic {
+    pub albedo: vec::Color,
+    pub emission: vec::Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: vec::Color) -> Isotropic {
+        Isotropic::with_emission(albedo, vec::Color::empty())
+    }
+
+    pub fn with_emission(albedo: vec::Color, emission: vec::Color) -> Isotropic {
+        Isotropic { albedo, emission }
+    }
+}
+
+impl Material for Isotropic {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let wo = -vec::unit_vector(ray.direction());
+        let wi = vec::random_unit_vector(rng);
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::DIFFUSE | Lobe::TRANSMISSION)
+    }
+
+    fn emitted(&self, _u: f32, _v: f32, _p: &vec::Point3) -> vec::Color {
+        self.emission
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // Phase function, there is no cosine term in a volume
+    fn eval(&self, _record: &hittable::HitRecord, _wo: &vec::Vec3, _wi: &vec::Vec3) -> vec::Color {
+        self.albedo / (4.0 * PI)
+    }
+
+    fn pdf(&self, _record: &hittable::HitRecord, _wo: &vec::Vec3, _wi: &vec::Vec3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// Glass with a GGX microfacet interface that both reflects and transmits, following Walter et al.
+// 2007, "Microfacet Models for Refraction through Rough Surfaces"
+pub struct RoughDielectric {
+    pub reflection_index: f32,
+    pub alpha: f32,
+    // Absorption coefficient of the interior per unit of distance
+    pub absorption: vec::Color,
+}
+
+impl RoughDielectric {
+    pub fn new(reflection_index: f32, roughness: f32) -> RoughDielectric {
+        RoughDielectric::with_absorption(reflection_index, roughness, vec::Color::empty())
+    }
+
+    pub fn with_absorption(
+        reflection_index: f32,
+        roughness: f32,
+        absorption: vec::Color,
+    ) -> RoughDielectric {
+        RoughDielectric {
+            reflection_index,
+            alpha: microfacet::roughness_to_alpha(roughness),
+            absorption,
+        }
+    }
+
+    // Index of refraction of the far side of the interface over the one of the near side
+    fn relative_index(&self, front_face: bool) -> f32 {
+        if front_face {
+            self.reflection_index
+        } else {
+            1.0 / self.reflection_index
+        }
+    }
+
+    // Microfacet normal between `wo` and `wi` in the local frame, on the side of +z
+    fn half_vector(&self, wo: &vec::Vec3, wi: &vec::Vec3, eta: f32) -> vec::Vec3 {
+        let h = if wi.z() > 0.0 {
+            *wo + *wi
+        } else {
+            *wo + *wi * eta
+        };
+        let h = vec::unit_vector(h);
+        if h.z() < 0.0 {
+            -h
+        } else {
+            h
+        }
+    }
+
+    // Direction reflected or refracted through a visible microfacet, in proportion to its
+    // reflectance. Reflections ending below the surface and refractions above it are dropped,
+    // since `eval` and `pdf` would take them for the other kind of event.
+    pub fn sample_direction(
+        &self,
+        normal: &vec::Vec3,
+        front_face: bool,
+        wo: &vec::Vec3,
+        rng: &mut ThreadRng,
+    ) -> Option<vec::Vec3> {
+        let frame = onb::Onb::build_from_w(normal);
+        let h = frame.local(&microfacet::sample_visible_normal(
+            &frame.to_local(wo),
+            self.alpha,
+            rng,
+        ));
+
+        let eta = self.relative_index(front_face);
+        let fresnel = microfacet::fresnel_dielectric(vec::dot(wo, &h), eta);
+        let (wi, reflected) = if random_double(rng) < fresnel {
+            (vec::reflect(&-*wo, &h), true)
+        } else {
+            (vec::unit_vector(vec::refract(&-*wo, &h, 1.0 / eta)), false)
+        };
+        if (vec::dot(&wi, normal) > 0.0) != reflected {
+            return None;
+        }
+        Some(wi)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let wo = -vec::unit_vector(ray.direction());
+        let wi = self.sample_direction(&record.normal, record.front_face, &wo, rng)?;
+        let side = if vec::dot(&wi, &record.normal) > 0.0 {
+            Lobe::REFLECTION
+        } else {
+            Lobe::TRANSMISSION
+        };
+        BsdfSample::evaluated(self, record, &wo, wi, Lobe::GLOSSY | side)
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.absorption
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return vec::Color::empty();
+        }
+
+        let eta = self.relative_index(record.front_face);
+        let h = self.half_vector(&wo, &wi, eta);
+        let (wo_h, wi_h) = (vec::dot(&wo, &h), vec::dot(&wi, &h));
+        // Back-facing microfacets don't contribute
+        if wo_h <= 0.0 || wi_h * wi.z() <= 0.0 {
+            return vec::Color::empty();
+        }
+
+        let fresnel = microfacet::fresnel_dielectric(wo_h, eta);
+        let dg = microfacet::d(&h, self.alpha) * microfacet::g2(&wo, &wi, self.alpha);
+        let value = if wi.z() > 0.0 {
+            fresnel * dg / (4.0 * wo.z())
+        } else {
+            // Walter et al. 2007 give the BTDF for importance, radiance is squeezed into a
+            // smaller solid angle on the denser side and picks up a factor 1 / eta^2 instead
+            let denom = wo_h + eta * wi_h;
+            (1.0 - fresnel) * dg * wo_h * wi_h.abs() / (wo.z() * denom * denom)
+        };
+        vec::Color::new(value, value, value)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        let eta = self.relative_index(record.front_face);
+        let h = self.half_vector(&wo, &wi, eta);
+        let (wo_h, wi_h) = (vec::dot(&wo, &h), vec::dot(&wi, &h));
+        if wo_h <= 0.0 || wi_h * wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let fresnel = microfacet::fresnel_dielectric(wo_h, eta);
+        let normal_pdf = microfacet::visible_normal_pdf(&wo, &h, self.alpha);
+        if wi.z() > 0.0 {
+            fresnel * normal_pdf / (4.0 * wo_h)
+        } else {
+            let denom = wo_h + eta * wi_h;
+            (1.0 - fresnel) * normal_pdf * eta * eta * wi_h.abs() / (denom * denom)
+        }
+    }
+}
+
+// Thin dielectric coat with its own roughness over any other material, like a varnish or the
+// clearcoat of car paint. Light either reflects off the coat, with its Fresnel reflectance, or
+// passes through it to interact with the base and is absorbed on its way in and out.
+pub struct Coated {
+    pub base: Rc<dyn Material>,
+    pub reflection_index: f32,
+    pub alpha: f32,
+    // Absorption of the coat along its thickness, at normal incidence
+    pub absorption: vec::Color,
+}
+
+impl Coated {
+    pub fn new(base: Rc<dyn Material>, reflection_index: f32, roughness: f32) -> Coated {
+        Coated::with_absorption(base, reflection_index, roughness, vec::Color::empty())
+    }
+
+    pub fn with_absorption(
+        base: Rc<dyn Material>,
+        reflection_index: f32,
+        roughness: f32,
+        absorption: vec::Color,
+    ) -> Coated {
+        Coated {
+            base,
+            reflection_index,
+            alpha: microfacet::roughness_to_alpha(roughness),
+            absorption,
+        }
+    }
+
+    // Chance of sampling the coat rather than the base, its reflectance seen from `wo`
+    fn coat_probability(&self, cos_o: f32) -> f32 {
+        microfacet::fresnel_dielectric(cos_o, self.reflection_index)
+    }
+
+    // Over a specular base, which can't be evaluated, the coat and the base are chosen with the
+    // Fresnel reflectance of the sampled microfacet, which cancels it from the weight
+    fn sample_specular_base(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let wo = -vec::unit_vector(ray.direction());
+        let local_wo = frame.to_local(&wo);
+        let h = frame.local(&microfacet::sample_visible_normal(
+            &local_wo, self.alpha, rng,
+        ));
+
+        let fresnel = microfacet::fresnel_dielectric(vec::dot(&wo, &h), self.reflection_index);
+        if random_double(rng) < fresnel {
+            let wi = vec::reflect(&-wo, &h);
+            let local_wi = frame.to_local(&wi);
+            if local_wi.z() <= 0.0 {
+                return None;
+            }
+            let masking = microfacet::g1(&local_wi, self.alpha);
+            return Some(BsdfSample {
+                direction: wi,
+                weight: vec::Color::new(masking, masking, masking),
+                pdf: fresnel,
+                lobe: Lobe::GLOSSY | Lobe::REFLECTION,
+            });
+        }
+
+        // The coat is thin, so the base is hit at the same point and the refraction through the
+        // coat doesn't offset the ray
+        let mut sample = self.base.sample(ray, record, rng)?;
+        sample.weight = sample.weight * self.coat_transmittance(local_wo.z());
+        sample.pdf *= 1.0 - fresnel;
+
+        // Light leaving through the coat is partly reflected back and lost
+        let cos_i = vec::dot(&sample.direction, &record.normal);
+        if cos_i > 0.0 {
+            let exit = 1.0 - microfacet::fresnel_dielectric(cos_i, self.reflection_index);
+            sample.weight = sample.weight * self.coat_transmittance(cos_i) * exit;
+        }
+        Some(sample)
+    }
+
+    // Fraction of light passing through the coat along a direction with the given cosine
+    fn coat_transmittance(&self, cos: f32) -> vec::Color {
+        let length = 1.0 / cos.abs().max(0.01);
+        vec::Color::new(
+            (-self.absorption.x() * length).exp(),
+            (-self.absorption.y() * length).exp(),
+            (-self.absorption.z() * length).exp(),
+        )
+    }
+}
+
+impl Material for Coated {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let wo = -vec::unit_vector(ray.direction());
+        let local_wo = frame.to_local(&wo);
+
+        if self.base.is_specular_at(record) {
+            return self.sample_specular_base(ray, record, rng);
+        }
+
+        // Pick the coat or the base, then weight by the whole layered BSDF so the direction can
+        // also be reached by light sampling
+        let (wi, lobe) = if random_double(rng) < self.coat_probability(local_wo.z()) {
+            let h = microfacet::sample_visible_normal(&local_wo, self.alpha, rng);
+            (
+                vec::reflect(&-wo, &frame.local(&h)),
+                Lobe::GLOSSY | Lobe::REFLECTION,
+            )
+        } else {
+            let sample = self.base.sample(ray, record, rng)?;
+            (sample.direction, sample.lobe)
+        };
+        BsdfSample::evaluated(self, record, &wo, wi, lobe)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn is_specular_at(&self, record: &hittable::HitRecord) -> bool {
+        self.base.is_specular_at(record)
+    }
+
+    // Light reaching the base loses the coat reflectance at normal incidence of both directions
+    // instead of averaging the Fresnel term over the rough coat
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (local_wo, local_wi) = (frame.to_local(wo), frame.to_local(wi));
+        if local_wo.z() <= 0.0 {
+            return vec::Color::empty();
+        }
+
+        let mut base = self.base.eval(record, wo, wi)
+            * self.coat_transmittance(local_wo.z())
+            * (1.0 - microfacet::fresnel_dielectric(local_wo.z(), self.reflection_index));
+        if local_wi.z() <= 0.0 {
+            return base;
+        }
+        base = base
+            * self.coat_transmittance(local_wi.z())
+            * (1.0 - microfacet::fresnel_dielectric(local_wi.z(), self.reflection_index));
+
+        let h = vec::unit_vector(local_wo + local_wi);
+        let coat = microfacet::fresnel_dielectric(vec::dot(&local_wo, &h), self.reflection_index)
+            * microfacet::d(&h, self.alpha)
+            * microfacet::g2(&local_wo, &local_wi, self.alpha)
+            / (4.0 * local_wo.z());
+        base + vec::Color::new(coat, coat, coat)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let (local_wo, local_wi) = (frame.to_local(wo), frame.to_local(wi));
+        if local_wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let coat = self.coat_probability(local_wo.z());
+        let mut pdf = (1.0 - coat) * self.base.pdf(record, wo, wi);
+        if local_wi.z() > 0.0 {
+            let h = vec::unit_vector(local_wo + local_wi);
+            pdf += coat * microfacet::visible_normal_pdf(&local_wo, &h, self.alpha)
+                / (4.0 * vec::dot(&local_wo, &h));
+        }
+        pdf
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &vec::Point3) -> vec::Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn opacity(&self, u: f32, v: f32, p: &vec::Point3) -> f32 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.base.interior_absorption()
+    }
+}
+
+// Step in texture coordinates for the finite differences of bump maps
+const BUMP_DELTA: f32 = 0.0005;
+
+// Perturbs the shading normal of a base material with a tangent-space normal map, whose red,
+// green and blue channels hold the normal along the tangent, bitangent and surface normal,
+// remapped from [-1, 1] to [0, 1]
+pub struct NormalMap {
+    pub base: Rc<dyn Material>,
+    pub map: Rc<dyn texture::Texture>,
+}
+
+impl NormalMap {
+    pub fn new(base: Rc<dyn Material>, map: Rc<dyn texture::Texture>) -> NormalMap {
+        NormalMap { base, map }
+    }
+
+    fn shading_record(&self, record: &hittable::HitRecord, wo: &vec::Vec3) -> hittable::HitRecord {
+        let n = self.map.value(record.u, record.v, &record.p) * 2.0 - 1.0;
+        perturbed(wo, record, &tangent_to_world(record, &n))
+    }
+}
+
+impl Material for NormalMap {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let record = self.shading_record(record, &-ray.direction());
+        self.base.sample(ray, &record, rng)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.base.interior_absorption()
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn is_specular_at(&self, record: &hittable::HitRecord) -> bool {
+        self.base.is_specular_at(record)
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        self.base.eval(&self.shading_record(record, wo), wo, wi)
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &vec::Point3) -> vec::Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn opacity(&self, u: f32, v: f32, p: &vec::Point3) -> f32 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        self.base.pdf(&self.shading_record(record, wo), wo, wi)
+    }
+}
+
+// Perturbs the shading normal of a base material along the slope of a height texture, `scale`
+// converts height differences per unit of texture coordinates into the tilt of the normal
+pub struct BumpMap {
+    pub base: Rc<dyn Material>,
+    pub height: Rc<dyn texture::Texture>,
+    pub scale: f32,
+}
+
+impl BumpMap {
+    pub fn new(base: Rc<dyn Material>, height: Rc<dyn texture::Texture>, scale: f32) -> BumpMap {
+        BumpMap {
+            base,
+            height,
+            scale,
+        }
+    }
+
+    fn shading_record(&self, record: &hittable::HitRecord, wo: &vec::Vec3) -> hittable::HitRecord {
+        let (u, v, p) = (record.u, record.v, &record.p);
+        let height = self.height.scalar(u, v, p);
+        let du = (self.height.scalar(u + BUMP_DELTA, v, p) - height) / BUMP_DELTA;
+        let dv = (self.height.scalar(u, v + BUMP_DELTA, p) - height) / BUMP_DELTA;
+
+        let n = vec::Vec3::new(-self.scale * du, -self.scale * dv, 1.0);
+        perturbed(wo, record, &tangent_to_world(record, &n))
+    }
+}
+
+impl Material for BumpMap {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let record = self.shading_record(record, &-ray.direction());
+        self.base.sample(ray, &record, rng)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.base.interior_absorption()
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn is_specular_at(&self, record: &hittable::HitRecord) -> bool {
+        self.base.is_specular_at(record)
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        self.base.eval(&self.shading_record(record, wo), wo, wi)
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &vec::Point3) -> vec::Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn opacity(&self, u: f32, v: f32, p: &vec::Point3) -> f32 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        self.base.pdf(&self.shading_record(record, wo), wo, wi)
+    }
+}
+
+// Normal given in the tangent space of the outside of the surface, turned to the side that was
+// hit. Building the frame on the flipped normal instead would mirror the map on back faces.
+fn tangent_to_world(record: &hittable::HitRecord, n: &vec::Vec3) -> vec::Vec3 {
+    let outward = if record.front_face {
+        record.normal
+    } else {
+        -record.normal
+    };
+    let n = onb::Onb::build_from_w_u(&outward, &record.tangent).local(n);
+    if record.front_face {
+        n
+    } else {
+        -n
+    }
+}
+
+// Copy of the record with the shading normal replaced. `front_face` still comes from the
+// geometric normal, and a normal turned away from `wo`, towards where the ray came from, is
+// ignored since it would scatter light from behind the surface.
+fn perturbed(
+    wo: &vec::Vec3,
+    record: &hittable::HitRecord,
+    normal: &vec::Vec3,
+) -> hittable::HitRecord {
+    let mut shading = record.clone();
+    let normal = vec::unit_vector(*normal);
+    if vec::dot(&normal, wo) > 0.0 {
+        shading.normal = normal;
+    }
+    shading
+}
+
+// Cuts out a base material where an opacity texture is below one, for leaves and fences
+// modeled as textured quads. Partial opacity lets that fraction of rays through.
+pub struct Masked {
+    pub base: Rc<dyn Material>,
+    pub opacity: Rc<dyn texture::Texture>,
+}
+
+impl Masked {
+    pub fn new(base: Rc<dyn Material>, opacity: Rc<dyn texture::Texture>) -> Masked {
+        Masked { base, opacity }
+    }
+}
+
+impl Material for Masked {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        self.base.sample(ray, record, rng)
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &vec::Point3) -> vec::Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn opacity(&self, u: f32, v: f32, p: &vec::Point3) -> f32 {
+        self.opacity.scalar(u, v, p) * self.base.opacity(u, v, p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn interior_absorption(&self) -> vec::Color {
+        self.base.interior_absorption()
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn is_specular_at(&self, record: &hittable::HitRecord) -> bool {
+        self.base.is_specular_at(record)
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        self.base.eval(record, wo, wi)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        self.base.pdf(record, wo, wi)
+    }
+}
+
+// Beer-Lambert attenuation of a ray that reached the back face of a dielectric, so traveled the
+// distance to the hit point inside of it
+pub fn interior_attenuation(
+    absorption: &vec::Color,
+    ray: &ray::Ray,
+    record: &hittable::HitRecord,
+) -> vec::Color {
+    if record.front_face {
+        return vec::Color::new(1.0, 1.0, 1.0);
+    }
+
+    let distance = record.t * ray.direction().length();
+    vec::Color::new(
+        (-absorption.x() * distance).exp(),
+        (-absorption.y() * distance).exp(),
+        (-absorption.z() * distance).exp(),
+    )
+}
+
 fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Hit on the xy plane, seen from above or below, with u along x
+    pub(crate) fn plane_record(front_face: bool) -> hittable::HitRecord {
+        let mut record = hittable::HitRecord::empty();
+        record.normal = vec::Vec3::new(0.0, 0.0, if front_face { 1.0 } else { -1.0 });
+        record.tangent = vec::Vec3::new(1.0, 0.0, 0.0);
+        record.front_face = front_face;
+        record
+    }
+
+    // Height rising along both u and v
+    struct Slope;
+
+    impl texture::Texture for Slope {
+        fn value(&self, u: f32, v: f32, _p: &vec::Point3) -> vec::Color {
+            let height = 0.3 * u + 0.2 * v;
+            vec::Color::new(height, height, height)
+        }
+    }
+
+    // The shading normal is a property of the surface, so the back face sees its opposite
+    fn assert_opposite(front: &vec::Vec3, back: &vec::Vec3) {
+        assert!((*front + *back).length() < 1e-5, "{} {}", front, back);
+        assert!(front.x().abs() > 0.1 && front.y().abs() > 0.1);
+    }
+
+    #[test]
+    fn maps_agree_on_both_faces() {
+        let base: Rc<dyn Material> = Rc::new(Lambertian {
+            albedo: vec::Color::new(0.5, 0.5, 0.5),
+        });
+        let (front, back) = (plane_record(true), plane_record(false));
+        let up = vec::Vec3::new(0.0, 0.0, 1.0);
+
+        let map = NormalMap::new(
+            base.clone(),
+            Rc::new(texture::SolidColor::new(vec::Color::new(0.7, 0.4, 0.9))),
+        );
+        assert_opposite(
+            &map.shading_record(&front, &up).normal,
+            &map.shading_record(&back, &-up).normal,
+        );
+
+        let bump = BumpMap::new(base, Rc::new(Slope), 1.0);
+        assert_opposite(
+            &bump.shading_record(&front, &up).normal,
+            &bump.shading_record(&back, &-up).normal,
+        );
+    }
+
+    // Unit direction `degrees` away from the normal of `record`, on the same side
+    pub(crate) fn towards(record: &hittable::HitRecord, degrees: f32) -> vec::Vec3 {
+        let theta = degrees_to_radians(degrees);
+        vec::Vec3::new(theta.sin(), 0.0, 0.0) + record.normal * theta.cos()
+    }
+
+    fn sample_from(
+        material: &dyn Material,
+        record: &hittable::HitRecord,
+        wo: &vec::Vec3,
+        rng: &mut ThreadRng,
+    ) -> Option<BsdfSample> {
+        let mut ray = ray::Ray::new(record.p + *wo, -*wo, 0.0);
+        material.sample(&mut ray, record, rng)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!(
+            (a - b).abs() <= 1e-3 * a.abs().max(b.abs()) + 1e-5,
+            "{} {}",
+            a,
+            b
+        );
+    }
+
+    // Checks that each direction sampled from `wo` carries the pdf given by `pdf` and a weight of
+    // `eval` over it. Returns the mean weight, the fraction of light the material scatters back.
+    pub(crate) fn check_samples(
+        material: &dyn Material,
+        record: &hittable::HitRecord,
+        wo: &vec::Vec3,
+        rng: &mut ThreadRng,
+    ) -> vec::Color {
+        // As materials find it from the ray, rounding and all, so both sides agree on directions
+        // where eval and pdf are steep
+        let wo = &-vec::unit_vector(-*wo);
+        let trials = 20000;
+        let mut sum = vec::Color::empty();
+        for _ in 0..trials {
+            let sample = match sample_from(material, record, wo, rng) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let pdf = material.pdf(record, wo, &sample.direction);
+            assert_close(sample.pdf, pdf);
+            let expected = material.eval(record, wo, &sample.direction) / pdf;
+            for c in 0..3 {
+                assert_close(sample.weight[c], expected[c]);
+            }
+            sum = sum + sample.weight;
+        }
+        sum / trials as f32
+    }
+
+    // Compares how often directions sampled from `wo` fall in each octant with the integral of
+    // `pdf` over it, taken over a grid of cells of equal solid angle
+    pub(crate) fn check_sampled_density(
+        material: &dyn Material,
+        record: &hittable::HitRecord,
+        wo: &vec::Vec3,
+        rng: &mut ThreadRng,
+    ) {
+        let octant = |d: &vec::Vec3| {
+            (d.x() > 0.0) as usize | ((d.y() > 0.0) as usize) << 1 | ((d.z() > 0.0) as usize) << 2
+        };
+
+        let trials = 100000;
+        let mut sampled = [0.0; 8];
+        for _ in 0..trials {
+            if let Some(sample) = sample_from(material, record, wo, rng) {
+                sampled[octant(&sample.direction)] += 1.0 / trials as f32;
+            }
+        }
+
+        let (rows, columns) = (2000, 200);
+        let cell = 4.0 * PI / (rows * columns) as f32;
+        let mut integrated = [0.0; 8];
+        for i in 0..rows {
+            let z = -1.0 + 2.0 * (i as f32 + 0.5) / rows as f32;
+            let r = (1.0 - z * z).sqrt();
+            for j in 0..columns {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / columns as f32;
+                let wi = vec::Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                integrated[octant(&wi)] += material.pdf(record, wo, &wi) * cell;
+            }
+        }
+
+        for (sampled, integrated) in sampled.iter().zip(integrated.iter()) {
+            assert!(
+                (sampled - integrated).abs() < 0.01,
+                "{} {}",
+                sampled,
+                integrated
+            );
+        }
+    }
+
+    // Checks the samples and their density for a range of angles on the outside of a surface, and
+    // that the material doesn't scatter more light than it receives under uniform lighting
+    pub(crate) fn check_reflective(material: &dyn Material) {
+        let mut rng = rand::thread_rng();
+        let record = plane_record(true);
+        for degrees in [0.0, 45.0, 80.0].iter() {
+            let wo = towards(&record, *degrees);
+            let albedo = check_samples(material, &record, &wo, &mut rng);
+            for c in 0..3 {
+                assert!(albedo[c] > 0.0 && albedo[c] <= 1.01, "{}", albedo);
+            }
+            check_sampled_density(material, &record, &wo, &mut rng);
+        }
+    }
+
+    #[test]
+    fn lambertian_samples_match_eval_and_pdf() {
+        let white = vec::Color::new(1.0, 1.0, 1.0);
+        check_reflective(&Lambertian::new(white));
+
+        let mut rng = rand::thread_rng();
+        let record = plane_record(true);
+        let wo = towards(&record, 45.0);
+        let albedo = check_samples(&Lambertian::new(white), &record, &wo, &mut rng);
+        assert!((albedo.x() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn specular_samples_carry_their_choice() {
+        let mut rng = rand::thread_rng();
+        let record = plane_record(true);
+        let wo = towards(&record, 45.0);
+        let mirrored = vec::Vec3::new(-wo.x(), wo.y(), wo.z());
+
+        // Mirrors can't be evaluated, and their only direction is sampled for sure
+        let albedo = vec::Color::new(0.9, 0.8, 0.7);
+        let mirror = Metal::new(albedo, 0.0);
+        assert!(mirror.is_specular());
+        assert_eq!(mirror.pdf(&record, &wo, &mirrored), 0.0);
+        let sample = sample_from(&mirror, &record, &wo, &mut rng).unwrap();
+        assert!(sample.is_specular());
+        assert_eq!(sample.pdf, 1.0);
+        assert!((sample.direction - mirrored).length() < 1e-5);
+        assert_eq!(sample.weight.y(), albedo.y());
+
+        // Glass picks reflection with the probability of its pdf, which the weight makes up for
+        let glass = Dielectric::new(1.5);
+        let trials = 100000;
+        let mut reflections = 0;
+        let mut reflection_pdf = 0.0;
+        for _ in 0..trials {
+            let sample = sample_from(&glass, &record, &wo, &mut rng).unwrap();
+            assert!(sample.is_specular());
+            assert_eq!(sample.weight.x(), 1.0);
+            if sample.lobe.contains(Lobe::REFLECTION) {
+                assert!((sample.direction - mirrored).length() < 1e-5);
+                reflections += 1;
+                reflection_pdf = sample.pdf;
+            } else {
+                assert!(sample.lobe.contains(Lobe::TRANSMISSION));
+                assert!(sample.direction.z() < 0.0);
+            }
+        }
+        assert!((reflections as f32 / trials as f32 - reflection_pdf).abs() < 0.005);
+    }
+
+    #[test]
+    fn conductor_samples_match_eval_and_pdf() {
+        check_reflective(&Conductor::gold(0.5));
+        check_reflective(&Conductor::aluminium(0.8));
+
+        // A nearly perfect reflector, which only loses the light scattered between microfacets
+        let white = Conductor::new(
+            vec::Color::new(0.01, 0.01, 0.01),
+            vec::Color::new(100.0, 100.0, 100.0),
+            0.5,
+        );
+        check_reflective(&white);
+        let mut rng = rand::thread_rng();
+        let record = plane_record(true);
+        let albedo = check_samples(&white, &record, &record.normal, &mut rng);
+        assert!(albedo.x() > 0.9);
+    }
+
+    #[test]
+    fn oren_nayar_samples_match_eval_and_pdf() {
+        let white = vec::Color::new(1.0, 1.0, 1.0);
+        check_reflective(&OrenNayar::new(white, 0.3));
+        check_reflective(&OrenNayar::new(white, 1.0));
+
+        // Without roughness it is Lambertian, scattering all light
+        let mut rng = rand::thread_rng();
+        let record = plane_record(true);
+        let wo = towards(&record, 45.0);
+        let albedo = check_samples(&OrenNayar::new(white, 0.0), &record, &wo, &mut rng);
+        assert!((albedo.x() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn coated_samples_match_eval_and_pdf() {
+        let white = vec::Color::new(1.0, 1.0, 1.0);
+        let diffuse: Rc<dyn Material> = Rc::new(Lambertian::new(white));
+        check_reflective(&Coated::new(diffuse.clone(), 1.5, 0.3));
+        check_reflective(&Coated::with_absorption(
+            diffuse,
+            1.5,
+            0.6,
+            vec::Color::new(0.1, 0.5, 1.0),
+        ));
+        check_reflective(&Coated::new(Rc::new(Conductor::gold(0.6)), 1.5, 0.4));
+    }
+
+    #[test]
+    fn rough_dielectric_samples_match_eval_and_pdf() {
+        let mut rng = rand::thread_rng();
+        let glass = RoughDielectric::new(1.5, 0.6);
+        for front_face in [true, false].iter() {
+            let record = plane_record(*front_face);
+            for degrees in [0.0, 45.0, 80.0].iter() {
+                let wo = towards(&record, *degrees);
+                let albedo = check_samples(&glass, &record, &wo, &mut rng);
+                // From the outside, the light refracted in is spread over a smaller solid angle
+                // but can't gain energy
+                if *front_face {
+                    assert!(albedo.x() <= 1.0);
+                }
+                check_sampled_density(&glass, &record, &wo, &mut rng);
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_transmits_radiance() {
+        // Radiance through an interface follows f(wo, wi) / eta_o^2 = f(wi, wo) / eta_i^2, with
+        // the cosine of the incoming direction taken out of `eval`
+        let glass = RoughDielectric::new(1.5, 0.4);
+        let (outside, inside) = (plane_record(true), plane_record(false));
+        for &(o, i) in [(0.0f32, 10.0f32), (30.0, 15.0), (60.0, 30.0)].iter() {
+            let wo = towards(&outside, o);
+            let wi = towards(&inside, -i);
+            let f_in = glass.eval(&outside, &wo, &wi).x() / wi.z().abs();
+            let f_out = glass.eval(&inside, &wi, &wo).x() / wo.z().abs();
+            assert!(f_in > 0.0);
+            assert_close(f_in * 1.5 * 1.5, f_out);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// GGX (Trowbridge-Reitz) microfacet distribution and Fresnel terms. Directions are given in a
+// local shading frame where the normal is +z.
+use crate::utility::*;
+use crate::vec::{self, Color, Vec3};
+use rand::prelude::*;
+
+// Roughness below this is treated as this, the distribution becomes singular at zero
+const MIN_ALPHA: f32 = 0.001;
+
+// Perceptual roughness in [0, 1] to the alpha parameter of the distribution
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(MIN_ALPHA)
+}
+
+// Density of microfacet normals `h`
+pub fn d(h: &Vec3, alpha: f32) -> f32 {
+    if h.z() <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let cos2 = h.z() * h.z();
+    let denom = cos2 * (a2 - 1.0) + 1.0;
+    a2 / (PI * denom * denom)
+}
+
+// Smith masking of direction `v`
+pub fn g1(v: &Vec3, alpha: f32) -> f32 {
+    let cos = v.z().abs();
+    let a2 = alpha * alpha;
+    2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+}
+
+// Separable masking-shadowing of the directions `wo` and `wi`
+pub fn g2(wo: &Vec3, wi: &Vec3, alpha: f32) -> f32 {
+    g1(wo, alpha) * g1(wi, alpha)
+}
+
+// Samples a microfacet normal visible from `wo`, following Heitz 2018, "Sampling the GGX
+// Distribution of Visible Normals"
+pub fn sample_visible_normal(wo: &Vec3, alpha: f32, rng: &mut ThreadRng) -> Vec3 {
+    // Stretch the view direction to the hemisphere configuration
+    let vh = vec::unit_vector(Vec3::new(alpha * wo.x(), alpha * wo.y(), wo.z()));
+
+    let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+    let t1 = if lensq > 0.0 {
+        Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = vec::cross(&vh, &t1);
+
+    // Uniform point on the projected disk, warped towards the visible half
+    let r = random_double(rng).sqrt();
+    let phi = 2.0 * PI * random_double(rng);
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z());
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+    let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    vec::unit_vector(Vec3::new(alpha * nh.x(), alpha * nh.y(), nh.z().max(0.0)))
+}
+
+// Density of the normals sampled by `sample_visible_normal`
+pub fn visible_normal_pdf(wo: &Vec3, h: &Vec3, alpha: f32) -> f32 {
+    if wo.z() <= 0.0 {
+        return 0.0;
+    }
+    g1(wo, alpha) * vec::dot(wo, h).max(0.0) * d(h, alpha) / wo.z()
+}
+
+// Fresnel reflectance of a dielectric interface, `eta` is the index of refraction of the
+// transmitted side over the one of the incident side
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.abs().min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction eta + i k, per channel
+pub fn fresnel_conductor(cos_i: f32, eta: &Color, k: &Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+    Color::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+// Schlick's approximation of the Fresnel falloff, (1 - cos)^5
+pub fn schlick_weight(cos: f32) -> f32 {
+    (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+// Generalized Trowbridge-Reitz distribution with exponent 1, the long-tailed lobe Burley 2012
+// uses for clearcoat
+pub fn d_gtr1(h: &Vec3, alpha: f32) -> f32 {
+    if h.z() <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let cos2 = h.z() * h.z();
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos2))
+}
+
+// Samples a microfacet normal proportionally to `d_gtr1` times its cosine
+pub fn sample_gtr1(alpha: f32, rng: &mut ThreadRng) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos2 = (1.0 - a2.powf(1.0 - random_double(rng))) / (1.0 - a2);
+    let cos = cos2.max(0.0).sqrt();
+    let sin = (1.0 - cos2).max(0.0).sqrt();
+    let phi = 2.0 * PI * random_double(rng);
+    Vec3::new(sin * phi.cos(), sin * phi.sin(), cos)
+}
@@ -0,0 +1,84 @@
+use crate::{aabb, hittable, material, ray, sphere, vec};
+use std::rc::Rc;
+
+// Sphere moving linearly from `center0` at `time0` to `center1` at `time1`
+pub struct MovingSphere {
+    center0: vec::Point3,
+    center1: vec::Point3,
+    time0: f32,
+    time1: f32,
+    radius: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: vec::Point3,
+        center1: vec::Point3,
+        time0: f32,
+        time1: f32,
+        radius: f32,
+        material: Rc<dyn material::Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f32) -> vec::Point3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        self.center0
+            + (self.center1 - self.center0) * ((time - self.time0) / (self.time1 - self.time0))
+    }
+}
+
+impl hittable::Hittable for MovingSphere {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let center = self.center(ray.time());
+        let oc = ray.origin() - center;
+        let a = ray.direction().length_squared();
+        let half_b = vec::dot(&oc, &ray.direction());
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+
+        if discriminant > 0.0 {
+            let root = discriminant.sqrt();
+
+            for temp in [(-half_b - root) / a, (-half_b + root) / a].iter() {
+                if *temp < *t_max && *temp > *t_min {
+                    record.t = *temp;
+                    record.p = ray.at(record.t);
+                    let outward_normal = (record.p - center) / self.radius;
+                    record.set_normale_face(ray, &outward_normal);
+                    sphere::set_sphere_uv(record, &outward_normal);
+                    record.material = self.material.clone();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let r = vec::Vec3::new(self.radius, self.radius, self.radius);
+        let (c0, c1) = (self.center(t0), self.center(t1));
+        *output_box = aabb::surrounding_box(
+            &aabb::Aabb::new(c0 - r, c0 + r),
+            &aabb::Aabb::new(c1 - r, c1 + r),
+        );
+        true
+    }
+}
@@ -0,0 +1,62 @@
+use crate::vec::{self, Vec3};
+
+// Orthonormal basis with `w` as the main axis
+#[derive(Copy, Clone)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: &Vec3) -> Onb {
+        let w = vec::unit_vector(*n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = vec::unit_vector(vec::cross(&w, &a));
+        let u = vec::cross(&v, &w);
+        Onb { u, v, w }
+    }
+
+    // Basis with `w` along `n` and `u` along the part of `tangent` perpendicular to it, or an
+    // arbitrary `u` if the tangent is zero or parallel to the normal
+    pub fn build_from_w_u(n: &Vec3, tangent: &Vec3) -> Onb {
+        let w = vec::unit_vector(*n);
+        let t = *tangent - w * vec::dot(tangent, &w);
+        if t.length_squared() < 1e-8 {
+            return Onb::build_from_w(n);
+        }
+        let u = vec::unit_vector(t);
+        let v = vec::cross(&w, &u);
+        Onb { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    // Local coordinates to world space
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    // World space to local coordinates
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(
+            vec::dot(a, &self.u),
+            vec::dot(a, &self.v),
+            vec::dot(a, &self.w),
+        )
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{aabb, hittable, material, onb, ray, vec, vec::*};
+use std::rc::Rc;
+
+// Infinite plane through `point`. It has no finite bounding box, so it is kept outside of the
+// BVH by `HittableList::into_bvh`.
+pub struct Plane {
+    point: Point3,
+    axes: onb::Onb,
+    material: Rc<dyn material::Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Rc<dyn material::Material>) -> Plane {
+        Plane {
+            point,
+            axes: onb::Onb::build_from_w(&normal),
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for Plane {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let normal = self.axes.w();
+        let denom = vec::dot(&normal, &ray.direction());
+
+        // If parallel to surface
+        if denom.abs() < f32::EPSILON {
+            return false;
+        }
+
+        // Intersect relative to the plane point to keep precision far from the origin
+        let t = vec::dot(&normal, &(self.point - ray.origin())) / denom;
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        record.t = t;
+        record.p = ray.at(t);
+        // Unbounded planar coordinates, textures are expected to tile them
+        let planar = record.p - self.point;
+        record.u = vec::dot(&planar, &self.axes.u());
+        record.v = vec::dot(&planar, &self.axes.v());
+        record.set_normale_face(ray, &normal);
+        record.tangent = self.axes.u();
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, _output_box: &mut aabb::Aabb) -> bool {
+        false
+    }
+}
@@ -0,0 +1,415 @@
+// Uber material after Burley 2012, "Physically-Based Shading at Disney", with the transmission
+// of Burley 2015. Its lobes are a Burley diffuse blended with an approximation of subsurface
+// scattering, sheen, a GGX specular, a GTR1 clearcoat and a rough dielectric transmission.
+use crate::material::{Lobe, Material};
+use crate::texture::{SolidColor, Texture};
+use crate::{color, hittable, material, microfacet, onb, ray, utility::*, vec};
+use rand::prelude::*;
+use std::rc::Rc;
+
+// Fixed roughness of the clearcoat masking
+const CLEARCOAT_MASKING_ALPHA: f32 = 0.25;
+
+pub struct Principled {
+    base_color: Rc<dyn Texture>,
+    metallic: Rc<dyn Texture>,
+    roughness: Rc<dyn Texture>,
+    specular: Rc<dyn Texture>,
+    sheen: Rc<dyn Texture>,
+    sheen_tint: Rc<dyn Texture>,
+    clearcoat: Rc<dyn Texture>,
+    clearcoat_gloss: Rc<dyn Texture>,
+    transmission: Rc<dyn Texture>,
+    subsurface: Rc<dyn Texture>,
+    reflection_index: f32,
+}
+
+// Parameters looked up at a hit point, with the weight of each lobe and the probability of
+// sampling it
+struct Lobes {
+    base_color: vec::Color,
+    roughness: f32,
+    alpha: f32,
+    specular_color: vec::Color,
+    sheen_color: vec::Color,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+    subsurface: f32,
+    diffuse_weight: f32,
+    specular_weight: f32,
+    transmission_weight: f32,
+    // Square root of the base color, applied at both interfaces a path crosses through the
+    // surface of a closed object so that the light comes out of it tinted once
+    transmission_color: vec::Color,
+    glass: material::RoughDielectric,
+    // Diffuse, specular, clearcoat and transmission
+    probabilities: [f32; 4],
+}
+
+impl Principled {
+    // Rough dielectric with the given base color, the other parameters are set with the `with_`
+    // methods
+    pub fn new(base_color: Rc<dyn Texture>) -> Principled {
+        let value = |x: f32| -> Rc<dyn Texture> { Rc::new(SolidColor::scalar(x)) };
+        Principled {
+            base_color,
+            metallic: value(0.0),
+            roughness: value(0.5),
+            specular: value(0.5),
+            sheen: value(0.0),
+            sheen_tint: value(0.5),
+            clearcoat: value(0.0),
+            clearcoat_gloss: value(1.0),
+            transmission: value(0.0),
+            subsurface: value(0.0),
+            reflection_index: 1.5,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Rc<dyn Texture>) -> Principled {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Rc<dyn Texture>) -> Principled {
+        self.roughness = roughness;
+        self
+    }
+
+    // Reflectance at normal incidence of dielectrics, 0.5 maps to 4%
+    pub fn with_specular(mut self, specular: Rc<dyn Texture>) -> Principled {
+        self.specular = specular;
+        self
+    }
+
+    // Retro-reflection at grazing angles for cloth, `tint` blends it from white to the base color
+    pub fn with_sheen(mut self, sheen: Rc<dyn Texture>, tint: Rc<dyn Texture>) -> Principled {
+        self.sheen = sheen;
+        self.sheen_tint = tint;
+        self
+    }
+
+    // Second specular layer, `gloss` goes from a rough to a smooth coat
+    pub fn with_clearcoat(
+        mut self,
+        clearcoat: Rc<dyn Texture>,
+        gloss: Rc<dyn Texture>,
+    ) -> Principled {
+        self.clearcoat = clearcoat;
+        self.clearcoat_gloss = gloss;
+        self
+    }
+
+    // Fraction of the dielectric base refracting light through the surface instead of diffusing it
+    pub fn with_transmission(
+        mut self,
+        transmission: Rc<dyn Texture>,
+        reflection_index: f32,
+    ) -> Principled {
+        self.transmission = transmission;
+        self.reflection_index = reflection_index;
+        self
+    }
+
+    // Blends the diffuse lobe into a flatter one approximating light scattered under the surface
+    pub fn with_subsurface(mut self, subsurface: Rc<dyn Texture>) -> Principled {
+        self.subsurface = subsurface;
+        self
+    }
+
+    fn lobes(&self, record: &hittable::HitRecord, wo: &vec::Vec3) -> Lobes {
+        let (u, v, p) = (record.u, record.v, &record.p);
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = self.metallic.scalar(u, v, p).clamp(0.0, 1.0);
+        let roughness = self.roughness.scalar(u, v, p).clamp(0.0, 1.0);
+        let transmission = self.transmission.scalar(u, v, p).clamp(0.0, 1.0);
+        let clearcoat = self.clearcoat.scalar(u, v, p).max(0.0);
+        let white = vec::Color::new(1.0, 1.0, 1.0);
+
+        let specular_color = lerp(
+            white * (0.08 * self.specular.scalar(u, v, p)),
+            base_color,
+            metallic,
+        );
+        let sheen_tint = self.sheen_tint.scalar(u, v, p);
+        let sheen_color = lerp(white, tint(&base_color), sheen_tint) * self.sheen.scalar(u, v, p);
+
+        // The back face is only reached from inside of a transmissive base, where the interface
+        // is the only lobe
+        let (diffuse_weight, specular_weight, transmission_weight, clearcoat) = if record.front_face
+        {
+            (
+                (1.0 - metallic) * (1.0 - transmission),
+                1.0 - (1.0 - metallic) * transmission,
+                (1.0 - metallic) * transmission,
+                clearcoat,
+            )
+        } else {
+            (0.0, 0.0, 1.0, 0.0)
+        };
+
+        let fresnel = microfacet::schlick_weight(wo.z());
+        let mut probabilities = [
+            diffuse_weight * color::luminance(&base_color),
+            specular_weight * color::luminance(&lerp(specular_color, white, fresnel)),
+            0.25 * clearcoat,
+            transmission_weight,
+        ];
+        let total: f32 = probabilities.iter().sum();
+        if total > 0.0 {
+            for probability in probabilities.iter_mut() {
+                *probability /= total;
+            }
+        }
+
+        Lobes {
+            base_color,
+            roughness,
+            alpha: microfacet::roughness_to_alpha(roughness),
+            specular_color,
+            sheen_color,
+            clearcoat,
+            clearcoat_alpha: lerp_scalar(0.1, 0.001, self.clearcoat_gloss.scalar(u, v, p)),
+            subsurface: self.subsurface.scalar(u, v, p).clamp(0.0, 1.0),
+            diffuse_weight,
+            specular_weight,
+            transmission_weight,
+            transmission_color: vec::Color::new(
+                base_color.x().max(0.0).sqrt(),
+                base_color.y().max(0.0).sqrt(),
+                base_color.z().max(0.0).sqrt(),
+            ),
+            glass: material::RoughDielectric::new(self.reflection_index, roughness),
+            probabilities,
+        }
+    }
+
+    fn eval_lobes(
+        &self,
+        lobes: &Lobes,
+        record: &hittable::HitRecord,
+        frame: &onb::Onb,
+        wo: &vec::Vec3,
+        wi: &vec::Vec3,
+    ) -> vec::Color {
+        let mut value = vec::Color::empty();
+        if lobes.transmission_weight > 0.0 {
+            let glass = lobes.glass.eval(record, wo, wi);
+            // Light refracted through the base takes its color
+            let tint = if vec::dot(wi, &record.normal) < 0.0 {
+                lobes.transmission_color
+            } else {
+                vec::Color::new(1.0, 1.0, 1.0)
+            };
+            value = value + glass * tint * lobes.transmission_weight;
+        }
+
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return value;
+        }
+        let h = vec::unit_vector(wo + wi);
+        let cos_d = vec::dot(&wi, &h);
+        let (fl, fv, fh) = (
+            microfacet::schlick_weight(wi.z()),
+            microfacet::schlick_weight(wo.z()),
+            microfacet::schlick_weight(cos_d),
+        );
+
+        if lobes.diffuse_weight > 0.0 {
+            // Burley diffuse, with retro-reflection at grazing angles on rough surfaces
+            let fd90 = 0.5 + 2.0 * lobes.roughness * cos_d * cos_d;
+            let fd = lerp_scalar(1.0, fd90, fl) * lerp_scalar(1.0, fd90, fv);
+            // Hanrahan-Krueger inspired flattening for subsurface scattering
+            let fss90 = lobes.roughness * cos_d * cos_d;
+            let fss = lerp_scalar(1.0, fss90, fl) * lerp_scalar(1.0, fss90, fv);
+            let ss = 1.25 * (fss * (1.0 / (wi.z() + wo.z()) - 0.5) + 0.5);
+
+            let diffuse = lobes.base_color * (lerp_scalar(fd, ss, lobes.subsurface) / PI)
+                + lobes.sheen_color * fh;
+            // Only light the specular layer lets through, on the way in and out, reaches the
+            // diffuse base, which keeps the sum of the lobes from reflecting more than comes in
+            let f0 = color::luminance(&lobes.specular_color);
+            let layer = (1.0 - lerp_scalar(f0, 1.0, fl)) * (1.0 - lerp_scalar(f0, 1.0, fv));
+            value = value + diffuse * (lobes.diffuse_weight * layer * wi.z());
+        }
+
+        if lobes.specular_weight > 0.0 {
+            let fresnel = lerp(lobes.specular_color, vec::Color::new(1.0, 1.0, 1.0), fh);
+            let dg = microfacet::d(&h, lobes.alpha) * microfacet::g2(&wo, &wi, lobes.alpha);
+            value = value + fresnel * (lobes.specular_weight * dg / (4.0 * wo.z()));
+        }
+
+        if lobes.clearcoat > 0.0 {
+            let fresnel = lerp_scalar(0.04, 1.0, fh);
+            let dg = microfacet::d_gtr1(&h, lobes.clearcoat_alpha)
+                * microfacet::g2(&wo, &wi, CLEARCOAT_MASKING_ALPHA);
+            let coat = 0.25 * lobes.clearcoat * fresnel * dg / (4.0 * wo.z());
+            value = value + vec::Color::new(coat, coat, coat);
+        }
+        value
+    }
+
+    fn pdf_lobes(
+        &self,
+        lobes: &Lobes,
+        record: &hittable::HitRecord,
+        frame: &onb::Onb,
+        wo: &vec::Vec3,
+        wi: &vec::Vec3,
+    ) -> f32 {
+        let [diffuse, specular, clearcoat, transmission] = lobes.probabilities;
+        let mut pdf = 0.0;
+        if transmission > 0.0 {
+            pdf += transmission * lobes.glass.pdf(record, wo, wi);
+        }
+
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return pdf;
+        }
+        let h = vec::unit_vector(wo + wi);
+        let wo_h = vec::dot(&wo, &h);
+        if wo_h <= 0.0 {
+            return pdf;
+        }
+
+        pdf += diffuse * wi.z() / PI;
+        pdf += specular * microfacet::visible_normal_pdf(&wo, &h, lobes.alpha) / (4.0 * wo_h);
+        pdf += clearcoat * microfacet::d_gtr1(&h, lobes.clearcoat_alpha) * h.z() / (4.0 * wo_h);
+        pdf
+    }
+}
+
+impl material::Material for Principled {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<material::BsdfSample> {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let wo = -vec::unit_vector(ray.direction());
+        let local_wo = frame.to_local(&wo);
+        let lobes = self.lobes(record, &local_wo);
+
+        // Pick one lobe to sample, the density of the direction accounts for all of them
+        let [diffuse, specular, clearcoat, _] = lobes.probabilities;
+        let choice = random_double(rng);
+        let (wi, lobe) = if choice < diffuse {
+            let wi = frame.local(&vec::random_cosine_direction(rng));
+            (wi, Lobe::DIFFUSE | Lobe::REFLECTION)
+        } else if choice < diffuse + specular {
+            let h = microfacet::sample_visible_normal(&local_wo, lobes.alpha, rng);
+            let wi = vec::reflect(&-wo, &frame.local(&h));
+            (wi, Lobe::GLOSSY | Lobe::REFLECTION)
+        } else if choice < diffuse + specular + clearcoat {
+            let h = microfacet::sample_gtr1(lobes.clearcoat_alpha, rng);
+            let wi = vec::reflect(&-wo, &frame.local(&h));
+            (wi, Lobe::GLOSSY | Lobe::REFLECTION)
+        } else {
+            let wi = lobes
+                .glass
+                .sample_direction(&record.normal, record.front_face, &wo, rng)?;
+            let side = if vec::dot(&wi, &record.normal) > 0.0 {
+                Lobe::REFLECTION
+            } else {
+                Lobe::TRANSMISSION
+            };
+            (wi, Lobe::GLOSSY | side)
+        };
+
+        // A reflection below the surface would be evaluated as a transmission
+        if lobe.contains(Lobe::REFLECTION) && vec::dot(&wi, &record.normal) <= 0.0 {
+            return None;
+        }
+
+        let pdf = self.pdf_lobes(&lobes, record, &frame, &wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(material::BsdfSample {
+            direction: wi,
+            weight: self.eval_lobes(&lobes, record, &frame, &wo, &wi) / pdf,
+            pdf,
+            lobe,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> vec::Color {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let lobes = self.lobes(record, &frame.to_local(wo));
+        self.eval_lobes(&lobes, record, &frame, wo, wi)
+    }
+
+    fn pdf(&self, record: &hittable::HitRecord, wo: &vec::Vec3, wi: &vec::Vec3) -> f32 {
+        let frame = onb::Onb::build_from_w(&record.normal);
+        let lobes = self.lobes(record, &frame.to_local(wo));
+        self.pdf_lobes(&lobes, record, &frame, wo, wi)
+    }
+}
+
+fn lerp(a: vec::Color, b: vec::Color, t: f32) -> vec::Color {
+    a * (1.0 - t) + b * t
+}
+
+fn lerp_scalar(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+// Hue and saturation of a color with unit luminance
+fn tint(color: &vec::Color) -> vec::Color {
+    let l = color::luminance(color);
+    if l > 0.0 {
+        *color / l
+    } else {
+        vec::Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::check_reflective;
+
+    fn white() -> Principled {
+        Principled::new(Rc::new(SolidColor::new(vec::Color::new(1.0, 1.0, 1.0))))
+    }
+
+    fn value(x: f32) -> Rc<dyn Texture> {
+        Rc::new(SolidColor::scalar(x))
+    }
+
+    // Each checks that sampled weights and pdfs match eval and pdf, that sampled directions follow
+    // pdf, and that a white surface reflects no more light than it receives
+
+    #[test]
+    fn dielectric_samples_match_eval_and_pdf() {
+        check_reflective(&white());
+        check_reflective(&white().with_specular(value(1.0)).with_roughness(value(1.0)));
+    }
+
+    #[test]
+    fn metallic_samples_match_eval_and_pdf() {
+        check_reflective(&white().with_metallic(value(1.0)));
+    }
+
+    #[test]
+    fn coat_and_sheen_samples_match_eval_and_pdf() {
+        check_reflective(
+            &white()
+                .with_clearcoat(value(1.0), value(0.0))
+                .with_sheen(value(1.0), value(0.5)),
+        );
+        check_reflective(&white().with_subsurface(value(1.0)));
+    }
+
+    #[test]
+    fn transmission_samples_match_eval_and_pdf() {
+        check_reflective(&white().with_transmission(value(1.0), 1.5));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{aabb, hittable, light_sampler, material, ray, utility::*, vec, vec::*};
+use rand::prelude::*;
+use std::rc::Rc;
+
+// Minimum extent of the bounding box along the axis a flat quad lies in
+const THICKNESS: f32 = 0.0001;
+
+// Parallelogram spanned by the edges `u` and `v` from the corner `q`
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    // n / (n . n), used to project hit points on the edges
+    w: Vec3,
+    normal: Vec3,
+    d: f32,
+    area: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Rc<dyn material::Material>) -> Quad {
+        let n = vec::cross(&u, &v);
+        let normal = vec::unit_vector(n);
+        Quad {
+            q,
+            u,
+            v,
+            w: n / vec::dot(&n, &n),
+            normal,
+            d: vec::dot(&normal, &q),
+            area: n.length(),
+            material,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.area
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    // Uniformly distributed point on the surface, the pdf with respect to area is 1 / area
+    pub fn sample(&self, rng: &mut ThreadRng) -> Point3 {
+        self.q + self.u * random_double(rng) + self.v * random_double(rng)
+    }
+}
+
+impl hittable::Hittable for Quad {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let denom = vec::dot(&self.normal, &ray.direction());
+
+        // If parallel to surface
+        if denom.abs() < f32::EPSILON {
+            return false;
+        }
+
+        let t = (self.d - vec::dot(&self.normal, &ray.origin())) / denom;
+        if !(*t_min..=*t_max).contains(&t) {
+            return false;
+        }
+
+        let p = ray.at(t);
+        let planar_hit = p - self.q;
+        let alpha = vec::dot(&self.w, &vec::cross(&planar_hit, &self.v));
+        let beta = vec::dot(&self.w, &vec::cross(&self.u, &planar_hit));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        record.u = alpha;
+        record.v = beta;
+        record.t = t;
+        record.p = p;
+        record.set_normale_face(ray, &self.normal);
+        record.tangent = self.u;
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let mut small = self.q;
+        let mut big = self.q;
+        for c in corners.iter() {
+            small = Point3::new(
+                small.x().min(c.x()),
+                small.y().min(c.y()),
+                small.z().min(c.z()),
+            );
+            big = Point3::new(big.x().max(c.x()), big.y().max(c.y()), big.z().max(c.z()));
+        }
+
+        let extent = big - small;
+        let pad_axis = |e: f32| if e < THICKNESS { THICKNESS } else { 0.0 };
+        let pad = Vec3::new(
+            pad_axis(extent.x()),
+            pad_axis(extent.y()),
+            pad_axis(extent.z()),
+        );
+        *output_box = aabb::Aabb::new(small - pad, big + pad);
+        true
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Points are sampled uniformly over the area, converted to solid angle at `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        let direction = vec::unit_vector(*direction);
+        let mut record = hittable::HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if !self.hit(
+            &ray::Ray::new(*origin, direction, 0.0),
+            &mut t_min,
+            &mut t_max,
+            &mut record,
+        ) {
+            return 0.0;
+        }
+
+        let cosine = vec::dot(&direction, &self.normal).abs();
+        record.t * record.t / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut ThreadRng) -> Vec3 {
+        self.sample(rng) - *origin
+    }
+
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        let mut bounds = aabb::Aabb::empty();
+        self.bounding_box(0.0, 0.0, &mut bounds);
+        let center = self.q + (self.u + self.v) * 0.5;
+        let radiance = self.material.emitted(0.5, 0.5, &center);
+        Some(light_sampler::LightBounds::flat(
+            bounds,
+            self.normal,
+            self.area,
+            &radiance,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+
+    fn quad(q: Point3, u: Vec3, v: Vec3) -> Quad {
+        Quad::new(q, u, v, Rc::new(material::Lambertian::new(Color::empty())))
+    }
+
+    // Hit of a ray going straight down onto the xy plane at (x, y)
+    fn hit_at(quad: &Quad, x: f32, y: f32) -> Option<hittable::HitRecord> {
+        let ray = ray::Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut record = hittable::HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if quad.hit(&ray, &mut t_min, &mut t_max, &mut record) {
+            Some(record)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn hits_inside_the_edges() {
+        // Slanted, so that parts of its bounding box are outside of it
+        let slanted = quad(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+
+        let record = hit_at(&slanted, 1.5, 0.5).unwrap();
+        assert!((record.t - 1.0).abs() < 1e-5);
+        assert!((record.u - 0.5).abs() < 1e-5 && (record.v - 0.5).abs() < 1e-5);
+        assert!(record.front_face);
+        assert!(hit_at(&slanted, 2.9, 0.95).is_some());
+
+        // Within the bounding box, past the slanted edges
+        assert!(hit_at(&slanted, 0.2, 0.8).is_none());
+        assert!(hit_at(&slanted, 2.8, 0.2).is_none());
+        // Past the straight edges
+        assert!(hit_at(&slanted, 1.5, -0.05).is_none());
+        assert!(hit_at(&slanted, 1.5, 1.05).is_none());
+    }
+
+    // Solid angle of a `width` by `height` rectangle seen from `distance` above one of its corners
+    fn corner_solid_angle(width: f32, height: f32, distance: f32) -> f32 {
+        let diagonal = (width * width + height * height + distance * distance).sqrt();
+        (width * height / (distance * diagonal)).atan()
+    }
+
+    #[test]
+    fn pdf_matches_sampled_directions() {
+        let mut rng = rand::thread_rng();
+        let rectangle = quad(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let origin = Point3::new(0.0, 0.0, 1.0);
+
+        // Weighting directions by the inverse of their pdf gives the solid angle they cover, of
+        // the whole quad and of the half nearest to `origin`
+        let trials = 100000;
+        let (mut whole, mut half) = (0.0, 0.0);
+        for _ in 0..trials {
+            let direction = rectangle.random(&origin, &mut rng);
+            let pdf = rectangle.pdf_value(&origin, &direction);
+            assert!(pdf > 0.0);
+            whole += 1.0 / pdf;
+            // Directions reach from `origin` to the sampled point
+            if (origin + direction).x() < 1.0 {
+                half += 1.0 / pdf;
+            }
+        }
+        let whole = whole / trials as f32;
+        let half = half / trials as f32;
+        assert!((whole / corner_solid_angle(2.0, 1.0, 1.0) - 1.0).abs() < 0.02);
+        assert!((half / corner_solid_angle(1.0, 1.0, 1.0) - 1.0).abs() < 0.02);
+
+        // Directions away from it aren't sampled
+        assert_eq!(rectangle.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+}
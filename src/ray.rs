@@ -3,17 +3,27 @@ use crate::vec::{Point3, Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f32,
+    // Wavelength in nanometers once the path has been made monochromatic by dispersion
+    wavelength: Option<f32>,
 }
 
 impl Ray {
-    pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: Point3, direction: Vec3, time: f32) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+            wavelength: None,
+        }
     }
 
     pub fn empty() -> Ray {
         Ray {
             origin: Point3::empty(),
             direction: Vec3::empty(),
+            time: 0.0,
+            wavelength: None,
         }
     }
 
@@ -25,6 +35,28 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
+
+    pub fn set_wavelength(&mut self, wavelength: f32) {
+        self.wavelength = Some(wavelength);
+    }
+
+    // Ray continuing the path of this one, keeping its time and wavelength
+    pub fn spawn(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time: self.time,
+            wavelength: self.wavelength,
+        }
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.origin + self.direction * t
     }
@@ -0,0 +1,1394 @@
+use rand::prelude::*;
+use ray_tracer::atmosphere::Atmosphere;
+use ray_tracer::box_shape::BoxShape;
+use ray_tracer::camera::Camera;
+use ray_tracer::cone::Cone;
+use ray_tracer::constant_medium::ConstantMedium;
+use ray_tracer::cylinder::Cylinder;
+use ray_tracer::disk::Disk;
+use ray_tracer::hittable::HittableList;
+use ray_tracer::instance::Translate;
+use ray_tracer::light::{DirectionalLight, Light, PointLight, SpotLight};
+use ray_tracer::light_sampler::{LightSampler, LightSelection};
+use ray_tracer::material::*;
+use ray_tracer::moving_sphere::MovingSphere;
+use ray_tracer::plane::Plane;
+use ray_tracer::principled::Principled;
+use ray_tracer::quad::Quad;
+use ray_tracer::sky::PhysicalSky;
+use ray_tracer::spectrum::Dispersion;
+use ray_tracer::sphere::Sphere;
+use ray_tracer::texture::{Checker, ImageTexture, SolidColor, Texture};
+use ray_tracer::torus::Torus;
+use ray_tracer::triangle::Triangle;
+use ray_tracer::utility::*;
+use ray_tracer::vec::{Color, Point3, Vec3};
+use ray_tracer::volume::{HeterogeneousMedium, VoxelGrid};
+use ray_tracer::{aabb, aarect, material, vec};
+use std::rc::Rc;
+
+pub struct Scene {
+    pub world: HittableList,
+    // Emissive objects of the world sampled directly, filled by `Scene::find_lights`
+    pub lights: LightSampler,
+    pub light_selection: LightSelection,
+    // Point, spot and directional lights, which aren't part of the world
+    pub analytic_lights: Vec<Box<dyn Light>>,
+    pub camera: Camera,
+    pub background: Background,
+    pub atmosphere: Option<Atmosphere>,
+}
+
+impl Scene {
+    pub fn new(world: HittableList, camera: Camera) -> Scene {
+        Scene {
+            world,
+            lights: LightSampler::new(HittableList::new(), LightSelection::Power),
+            light_selection: LightSelection::Power,
+            analytic_lights: vec![],
+            camera,
+            background: Background::Gradient,
+            atmosphere: None,
+        }
+    }
+
+    // Daylight sky as the background, along with its sun
+    pub fn set_sky(&mut self, sky: PhysicalSky) {
+        self.analytic_lights.push(Box::new(sky.sun()));
+        self.background = Background::Sky(sky);
+    }
+
+    pub fn find_lights(&mut self) {
+        self.lights = LightSampler::new(self.world.lights(), self.light_selection);
+    }
+}
+
+// Radiance of rays escaping the scene
+pub enum Background {
+    // White to light blue sky
+    Gradient,
+    Solid(Color),
+    Sky(PhysicalSky),
+}
+
+impl Background {
+    pub fn color(&self, direction: &Vec3) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_direction = vec::unit_vector(*direction);
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+            }
+            Background::Solid(color) => *color,
+            Background::Sky(sky) => sky.radiance(direction),
+        }
+    }
+}
+
+pub fn random_scene(rng: &mut ThreadRng, ascpect_ratio: f32) -> Scene {
+    let world = random_world(rng, false);
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        ascpect_ratio,
+        0.1,
+        10.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Random scene with the diffuse spheres bouncing up while the shutter is open
+pub fn motion_blur(rng: &mut ThreadRng, ascpect_ratio: f32) -> Scene {
+    let mut world = random_world(rng, true);
+
+    let cube = BoxShape::new(
+        Point3::new(-0.5, 0.0, -0.5),
+        Point3::new(0.5, 1.0, 0.5),
+        Rc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1))),
+    );
+    world.add(Box::new(Translate::moving(
+        Box::new(cube),
+        Vec3::new(6.0, 0.0, 2.0),
+        Vec3::new(6.0, 0.0, 1.4),
+        0.0,
+        1.0,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        ascpect_ratio,
+        0.1,
+        10.0,
+    )
+    .with_shutter(0.0, 1.0);
+
+    Scene::new(world, camera)
+}
+
+fn random_world(rng: &mut ThreadRng, moving: bool) -> HittableList {
+    let mut world = HittableList::new();
+    let material_ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        material_ground,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_double(rng);
+            let center = Point3::new(
+                a as f32 + 0.9 * random_double(rng),
+                0.2,
+                b as f32 + 0.9 * random_double(rng),
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let material: Rc<dyn material::Material>;
+                if choose_mat < 0.8 {
+                    let albedo = Color::random(rng) * Color::random(rng);
+                    material = Rc::new(Lambertian::new(albedo));
+                    if moving {
+                        let center1 =
+                            center + Vec3::new(0.0, random_double_range(rng, 0.0, 0.5), 0.0);
+                        world.add(Box::new(MovingSphere::new(
+                            center, center1, 0.0, 1.0, 0.2, material,
+                        )));
+                        continue;
+                    }
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_range(rng, 0.5, 1.0);
+                    let fuzz = random_double_range(rng, 0.0, 0.5);
+                    material = Rc::new(Metal::new(albedo, fuzz));
+                } else {
+                    material = Rc::new(Dielectric::new(1.5));
+                }
+                world.add(Box::new(Sphere::new(center, 0.2, material)));
+            }
+        }
+    }
+
+    let material1 = Rc::new(Dielectric::new(1.5));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+    world
+}
+
+pub fn boxes(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+    let wall = Rc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let red = Rc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let mirror = Rc::new(Metal::new(Color::new(0.8, 0.85, 0.88), 0.0));
+    let rug = Rc::new(Lambertian::new(Color::new(0.2, 0.3, 0.7)));
+
+    world.add(Box::new(aarect::XzRect::new(
+        -10.0, 10.0, -10.0, 10.0, 0.0, ground,
+    )));
+    world.add(Box::new(aarect::XyRect::new(
+        -4.0, 4.0, 0.0, 3.0, -3.0, wall,
+    )));
+    world.add(Box::new(aarect::YzRect::new(
+        0.0, 3.0, -3.0, 3.0, -4.0, mirror,
+    )));
+
+    world.add(Box::new(Disk::new(
+        Point3::new(0.0, 0.001, 1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        1.8,
+        rug,
+    )));
+
+    world.add(Box::new(BoxShape::new(
+        Point3::new(-1.5, 0.0, -1.0),
+        Point3::new(-0.5, 2.0, 0.0),
+        red.clone(),
+    )));
+    world.add(Box::new(BoxShape::new(
+        Point3::new(0.5, 0.0, 0.0),
+        Point3::new(1.5, 1.0, 1.0),
+        red,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(6.0, 3.0, 8.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        35.0,
+        ascpect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+pub fn quads(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let left_red = Rc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
+    let back_green = Rc::new(Lambertian::new(Color::new(0.2, 1.0, 0.2)));
+    let right_blue = Rc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
+    let upper_orange = Rc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
+    let lower_teal = Rc::new(Metal::new(Color::new(0.2, 0.8, 0.8), 0.1));
+
+    world.add(Box::new(Quad::new(
+        Point3::new(-3.0, -2.0, 5.0),
+        Vec3::new(0.0, 0.0, -4.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        left_red,
+    )));
+    world.add(Box::new(Quad::new(
+        Point3::new(-2.0, -2.0, 0.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(1.0, 4.0, 0.0),
+        back_green,
+    )));
+    world.add(Box::new(Quad::new(
+        Point3::new(3.0, -2.0, 1.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        right_blue,
+    )));
+    world.add(Box::new(Quad::new(
+        Point3::new(-2.0, 3.0, 1.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        upper_orange,
+    )));
+    world.add(Box::new(Quad::new(
+        Point3::new(-2.0, -3.0, 5.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -4.0),
+        lower_teal,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 0.0, 9.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        80.0,
+        ascpect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+pub fn shapes(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let copper = Rc::new(Metal::new(Color::new(0.95, 0.64, 0.54), 0.2));
+    let blue = Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.6)));
+    let glass = Rc::new(Dielectric::new(1.5));
+
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+    world.add(Box::new(Cylinder::new(
+        Point3::new(-2.5, 0.0, 0.0),
+        Vec3::new(0.0, 1.5, 0.0),
+        0.6,
+        true,
+        blue.clone(),
+    )));
+    world.add(Box::new(Cone::new(
+        Point3::new(0.0, 0.0, -0.5),
+        Vec3::new(0.0, 2.0, 0.0),
+        0.8,
+        true,
+        copper,
+    )));
+    world.add(Box::new(Torus::new(
+        Point3::new(2.5, 0.35, 0.0),
+        Vec3::new(0.0, 1.0, 0.3),
+        0.9,
+        0.3,
+        blue,
+    )));
+    world.add(Box::new(Cylinder::new(
+        Point3::new(1.0, 0.0, 1.8),
+        Vec3::new(0.0, 0.8, 0.0),
+        0.4,
+        false,
+        glass,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 3.0, 8.0),
+        Point3::new(0.0, 0.7, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        8.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+pub fn volumes(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+    let boundary = Rc::new(Lambertian::new(Color::empty()));
+
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let smoke_box = BoxShape::new(
+        Point3::new(-2.5, 0.0, -1.0),
+        Point3::new(-0.5, 2.5, 1.0),
+        boundary.clone(),
+    );
+    world.add(Box::new(ConstantMedium::new(
+        Box::new(smoke_box),
+        0.8,
+        Color::new(0.1, 0.1, 0.1),
+    )));
+
+    let fog_sphere = Sphere::new(Point3::new(1.5, 1.2, 0.0), 1.2, boundary);
+    world.add(Box::new(ConstantMedium::new(
+        Box::new(fog_sphere),
+        1.5,
+        Color::new(0.9, 0.9, 0.95),
+    )));
+
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.3, 0.5, 2.0),
+        0.5,
+        Rc::new(Dielectric::new(1.5)),
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 2.0, 9.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        9.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Heterogeneous volume read from the voxel grid file at `grid_path`, or a procedural fireball
+pub fn cloud(ascpect_ratio: f32, grid_path: Option<String>) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let grid = match grid_path {
+        Some(path) => VoxelGrid::load(&path).unwrap_or_else(|e| {
+            eprintln!("Can't load voxel grid {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => fireball_grid(48),
+    };
+    world.add(Box::new(HeterogeneousMedium::new(
+        grid,
+        aabb::Aabb::new(Point3::new(-2.0, 0.0, -2.0), Point3::new(2.0, 4.0, 2.0)),
+        4.0,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 3.0, 10.0),
+        Point3::new(0.0, 2.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        10.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Billowing sphere of smoke around a glowing core
+fn fireball_grid(n: usize) -> VoxelGrid {
+    let count = n * n * n;
+    let mut density = Vec::with_capacity(count);
+    let mut albedo = Vec::with_capacity(count);
+    let mut emission = Vec::with_capacity(count);
+
+    for z in 0..n {
+        for y in 0..n {
+            for x in 0..n {
+                let p = Vec3::new(x as f32, y as f32, z as f32) / (n - 1) as f32 * 2.0 - 1.0;
+                let r = p.length();
+                let billows = 0.15
+                    * ((p.x() * 9.0).sin() * (p.y() * 7.0).sin() * (p.z() * 8.0).sin()
+                        + 0.5 * (p.x() * 17.0 + p.z() * 13.0).sin());
+                let shell = (1.0 - (r + billows) / 0.9).max(0.0);
+                density.push(shell.powf(0.6));
+
+                let heat = (1.0 - r / 0.45).max(0.0);
+                albedo.push(Color::new(0.7, 0.7, 0.7) * (1.0 - heat));
+                emission.push(Color::new(4.0, 1.5, 0.3) * heat * heat);
+            }
+        }
+    }
+
+    VoxelGrid::new((n, n, n), density, albedo, emission)
+}
+
+// Random scene in a hazy atmosphere lit by an area light shining between the spheres
+pub fn haze(rng: &mut ThreadRng, ascpect_ratio: f32) -> Scene {
+    let mut world = random_world(rng, false);
+
+    let light = Rc::new(DiffuseLight::new(Color::new(15.0, 14.0, 12.0)));
+    world.add(Box::new(Quad::new(
+        Point3::new(-4.0, 7.0, -4.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        light,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        ascpect_ratio,
+        0.1,
+        10.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.02, 0.02, 0.04));
+    scene.atmosphere = Some(Atmosphere::new(
+        Color::new(0.002, 0.002, 0.002),
+        Color::new(0.02, 0.025, 0.03),
+    ));
+    scene
+}
+
+// Rows of microfacet metals going from polished to rough
+pub fn metals(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.3, 0.3, 0.35)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let presets: [fn(f32) -> Conductor; 4] = [
+        Conductor::gold,
+        Conductor::copper,
+        Conductor::aluminium,
+        Conductor::silver,
+    ];
+    for (row, preset) in presets.iter().enumerate() {
+        for column in 0..5 {
+            let roughness = column as f32 / 4.0;
+            world.add(Box::new(Sphere::new(
+                Point3::new(column as f32 * 1.1 - 2.2, 0.5, row as f32 * -1.1),
+                0.5,
+                Rc::new(preset(roughness)),
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 4.0, 6.0),
+        Point3::new(0.0, 0.3, -1.5),
+        Vec3::new(0.0, 1.0, 0.0),
+        35.0,
+        ascpect_ratio,
+        0.0,
+        8.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Glass spheres going from smooth to frosted in front of colored stripes
+pub fn frosted_glass(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let stripes = [
+        Color::new(0.8, 0.1, 0.1),
+        Color::new(0.9, 0.8, 0.1),
+        Color::new(0.1, 0.6, 0.2),
+        Color::new(0.1, 0.3, 0.8),
+    ];
+    for (i, color) in stripes.iter().enumerate() {
+        let x0 = i as f32 * 1.5 - 3.0;
+        world.add(Box::new(aarect::XyRect::new(
+            x0,
+            x0 + 0.75,
+            0.0,
+            3.0,
+            -2.0,
+            Rc::new(Lambertian::new(*color)),
+        )));
+    }
+
+    for (i, roughness) in [0.0, 0.1, 0.25, 0.5].iter().enumerate() {
+        world.add(Box::new(Sphere::new(
+            Point3::new(i as f32 * 1.4 - 2.1, 0.6, 0.0),
+            0.6,
+            Rc::new(RoughDielectric::new(1.5, *roughness)),
+        )));
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 1.5, 7.0),
+        Point3::new(0.0, 0.7, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        35.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Colored glass and liquid tinted by absorption along the path through them
+pub fn tinted_glass(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    world.add(Box::new(Sphere::new(
+        Point3::new(-2.2, 0.8, 0.0),
+        0.8,
+        Rc::new(Dielectric::with_absorption(1.5, Color::new(0.1, 1.2, 1.8))),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(-0.3, 0.5, 0.6),
+        0.5,
+        Rc::new(Dielectric::with_absorption(1.5, Color::new(2.5, 0.6, 0.1))),
+    )));
+    world.add(Box::new(Cylinder::new(
+        Point3::new(1.5, 0.0, 0.0),
+        Vec3::new(0.0, 1.6, 0.0),
+        0.6,
+        true,
+        Rc::new(Dielectric::with_absorption(
+            1.33,
+            Color::new(0.05, 0.9, 0.3),
+        )),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(3.0, 0.6, 0.8),
+        0.6,
+        Rc::new(RoughDielectric::with_absorption(
+            1.5,
+            0.3,
+            Color::new(1.5, 1.5, 0.2),
+        )),
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 2.0, 7.0),
+        Point3::new(0.3, 0.6, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Dispersive glass throwing rainbow caustics on the floor
+pub fn dispersion(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let floor = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        floor,
+    )));
+
+    let light = Rc::new(DiffuseLight::new(Color::new(40.0, 40.0, 40.0)));
+    world.add(Box::new(Quad::new(
+        Point3::new(-4.5, 5.0, -0.5),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        light,
+    )));
+
+    world.add(Box::new(Sphere::new(
+        Point3::new(-1.0, 1.0, 0.0),
+        1.0,
+        Rc::new(Dielectric::dispersive(Dispersion::sf11())),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(1.5, 0.6, 0.5),
+        0.6,
+        Rc::new(Dielectric::dispersive(Dispersion::diamond())),
+    )));
+
+    let camera = Camera::new(
+        Point3::new(2.0, 4.0, 7.0),
+        Point3::new(0.5, 0.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.01, 0.01, 0.01));
+    scene
+}
+
+// Rows of spheres sweeping the parameters of the principled material over a checkered floor
+pub fn principled(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+    let value = |x: f32| -> Rc<dyn Texture> { Rc::new(SolidColor::scalar(x)) };
+    let color = |r: f32, g: f32, b: f32| -> Rc<dyn Texture> {
+        Rc::new(SolidColor::new(Color::new(r, g, b)))
+    };
+
+    let checker = Rc::new(Checker::new(
+        color(0.2, 0.2, 0.2),
+        color(0.8, 0.8, 0.8),
+        1.0,
+    ));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Rc::new(Principled::new(checker).with_roughness(value(0.3))),
+    )));
+
+    for column in 0..5 {
+        let t = column as f32 / 4.0;
+        let rows = vec![
+            // Plastic to metal
+            Principled::new(color(0.9, 0.5, 0.2))
+                .with_metallic(value(t))
+                .with_roughness(value(0.3)),
+            // Smooth to rough
+            Principled::new(color(0.2, 0.4, 0.9)).with_roughness(value(t)),
+            // Car paint clearcoat over a rough base
+            Principled::new(color(0.7, 0.05, 0.05))
+                .with_roughness(value(0.6))
+                .with_clearcoat(value(t), value(0.9)),
+            // Velvet sheen and subsurface
+            Principled::new(color(0.5, 0.1, 0.4))
+                .with_roughness(value(1.0))
+                .with_sheen(value(2.0 * t), value(0.5))
+                .with_subsurface(value(t)),
+            // Opaque to glass
+            Principled::new(color(0.8, 1.0, 0.9))
+                .with_roughness(value(0.1))
+                .with_transmission(value(t), 1.5),
+        ];
+        for (row, material) in rows.into_iter().enumerate() {
+            world.add(Box::new(Sphere::new(
+                Point3::new(t * 4.4 - 2.2, 0.5, row as f32 * -1.1),
+                0.5,
+                Rc::new(material),
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 4.5, 6.0),
+        Point3::new(0.0, 0.3, -2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        38.0,
+        ascpect_ratio,
+        0.0,
+        8.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Paints and varnishes: the same bases bare and under coats of increasing roughness
+pub fn coated(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let floor = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        floor,
+    )));
+
+    let paint: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(0.6, 0.02, 0.02)));
+    let wood: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(0.45, 0.25, 0.1)));
+    let metal: Rc<dyn Material> = Rc::new(Conductor::aluminium(0.5));
+    let amber = Color::new(0.05, 0.2, 0.6);
+
+    for column in 0..5 {
+        let x = column as f32 * 1.1 - 2.2;
+        let rows: Vec<Rc<dyn Material>> = if column == 0 {
+            vec![paint.clone(), wood.clone(), metal.clone()]
+        } else {
+            let roughness = (column - 1) as f32 / 6.0;
+            vec![
+                Rc::new(Coated::new(paint.clone(), 1.5, roughness)),
+                Rc::new(Coated::with_absorption(wood.clone(), 1.5, roughness, amber)),
+                Rc::new(Coated::new(metal.clone(), 1.5, roughness)),
+            ]
+        };
+        for (row, material) in rows.into_iter().enumerate() {
+            world.add(Box::new(Sphere::new(
+                Point3::new(x, 0.5, row as f32 * -1.1),
+                0.5,
+                material,
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 3.5, 6.0),
+        Point3::new(0.0, 0.3, -1.1),
+        Vec3::new(0.0, 1.0, 0.0),
+        35.0,
+        ascpect_ratio,
+        0.0,
+        8.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Clay spheres going from Lambertian to very rough Oren-Nayar, lit from behind the camera
+pub fn rough_diffuse(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let floor = Rc::new(OrenNayar::new(Color::new(0.5, 0.5, 0.5), 0.5));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        floor,
+    )));
+
+    let light = Rc::new(DiffuseLight::new(Color::new(8.0, 8.0, 8.0)));
+    world.add(Box::new(Quad::new(
+        Point3::new(-4.0, 1.0, 9.0),
+        Vec3::new(8.0, 0.0, 0.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        light,
+    )));
+
+    let clay = Color::new(0.75, 0.45, 0.3);
+    world.add(Box::new(Sphere::new(
+        Point3::new(-3.3, 0.6, 0.0),
+        0.6,
+        Rc::new(Lambertian::new(clay)),
+    )));
+    for i in 1..5 {
+        let sigma = degrees_to_radians(i as f32 * 15.0);
+        world.add(Box::new(Sphere::new(
+            Point3::new(i as f32 * 1.35 - 3.3, 0.6, 0.0),
+            0.6,
+            Rc::new(OrenNayar::new(clay, sigma)),
+        )));
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 1.5, 7.0),
+        Point3::new(0.0, 0.6, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.05, 0.05, 0.07));
+    scene
+}
+
+// Height field of concentric waves around every integer point of the texture coordinates
+struct Ripples {
+    frequency: f32,
+}
+
+impl Texture for Ripples {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        let (x, y) = (u - u.round(), v - v.round());
+        let h = 0.5 + 0.5 * (2.0 * PI * self.frequency * (x * x + y * y).sqrt()).cos();
+        Color::new(h, h, h)
+    }
+}
+
+// Tangent-space normal map of bricks separated by sunken mortar, computed from their height
+fn brick_normal_map(size: usize) -> ImageTexture {
+    let height = |x: f32, y: f32| {
+        let row = (y * 8.0).floor();
+        let bx = x * 4.0 + if row as i32 % 2 == 0 { 0.0 } else { 0.5 };
+        let (fx, fy) = (bx - bx.floor(), y * 8.0 - row);
+        // Distance to the closest mortar line in units of brick height
+        let edge = (fx.min(1.0 - fx) * 2.0).min(fy.min(1.0 - fy));
+        (edge / 0.08).min(1.0)
+    };
+
+    let step = 1.0 / size as f32;
+    let mut pixels = Vec::with_capacity(size * size);
+    for j in 0..size {
+        for i in 0..size {
+            let (x, y) = (i as f32 * step, 1.0 - j as f32 * step);
+            let dx = (height(x + step, y) - height(x - step, y)) / (2.0 * step);
+            let dy = (height(x, y + step) - height(x, y - step)) / (2.0 * step);
+            let n = vec::unit_vector(Vec3::new(-0.05 * dx, -0.05 * dy, 1.0));
+            pixels.push((n + 1.0) * 0.5);
+        }
+    }
+    ImageTexture::new(size, size, pixels)
+}
+
+// Flat primitives and spheres with shading normals from normal and bump maps. The normal map
+// can be read from a PPM file, bricks are generated otherwise.
+pub fn bumps(ascpect_ratio: f32, normal_map_path: Option<String>) -> Scene {
+    let mut world = HittableList::new();
+
+    let normal_map: Rc<dyn Texture> = match normal_map_path {
+        Some(path) => Rc::new(ImageTexture::load(&path).unwrap_or_else(|e| {
+            panic!("Can't read normal map {}: {}", path, e);
+        })),
+        None => Rc::new(brick_normal_map(512)),
+    };
+    let ripples: Rc<dyn Texture> = Rc::new(Ripples { frequency: 6.0 });
+
+    let sand: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(0.7, 0.6, 0.45)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Rc::new(BumpMap::new(sand, ripples.clone(), 0.02)),
+    )));
+
+    // Wall of two triangles with texture coordinates spanning the normal map twice
+    let brick: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(0.6, 0.25, 0.15)));
+    let wall: Rc<dyn Material> = Rc::new(NormalMap::new(brick.clone(), normal_map.clone()));
+    let corners = [
+        Point3::new(-4.0, 0.0, -3.0),
+        Point3::new(4.0, 0.0, -3.0),
+        Point3::new(4.0, 4.0, -3.0),
+        Point3::new(-4.0, 4.0, -3.0),
+    ];
+    world.add(Box::new(Triangle::with_uvs(
+        [corners[0], corners[1], corners[2]],
+        [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0)],
+        wall.clone(),
+    )));
+    world.add(Box::new(Triangle::with_uvs(
+        [corners[0], corners[2], corners[3]],
+        [(0.0, 0.0), (2.0, 1.0), (0.0, 1.0)],
+        wall,
+    )));
+
+    world.add(Box::new(Sphere::new(
+        Point3::new(-1.6, 1.0, 0.0),
+        1.0,
+        Rc::new(NormalMap::new(brick, normal_map)),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(1.6, 1.0, 0.0),
+        1.0,
+        Rc::new(BumpMap::new(Rc::new(Conductor::gold(0.2)), ripples, 0.01)),
+    )));
+
+    let light = Rc::new(DiffuseLight::new(Color::new(6.0, 6.0, 6.0)));
+    world.add(Box::new(Quad::new(
+        Point3::new(-6.0, 6.0, 2.0),
+        Vec3::new(3.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 3.0),
+        light,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 2.5, 7.0),
+        Point3::new(0.0, 1.2, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        ascpect_ratio,
+        0.0,
+        8.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Opacity of a chain-link fence, wires along both diagonals of each cell
+struct ChainLink {
+    cells: f32,
+    wire: f32,
+}
+
+impl Texture for ChainLink {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        let wire = |x: f32| {
+            let f = x * self.cells;
+            (f - f.round()).abs() < self.wire
+        };
+        if wire(u + v) || wire(u - v) {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            Color::empty()
+        }
+    }
+}
+
+// Opacity of a leaf shaped like a lens between two arcs across the texture square
+struct Leaf;
+
+impl Texture for Leaf {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        let (x, y) = (u - 0.5, v - 0.5);
+        let half_width = 0.35 * (1.0 - 4.0 * y * y);
+        if x.abs() < half_width {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            Color::empty()
+        }
+    }
+}
+
+// Chain-link fence and leaves cut out of quads, and a half transparent curtain, in front of a
+// few spheres
+pub fn cutouts(rng: &mut ThreadRng, ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.4, 0.45, 0.35)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let steel: Rc<dyn Material> = Rc::new(Conductor::iron(0.4));
+    world.add(Box::new(Quad::new(
+        Point3::new(-4.0, 0.0, 1.0),
+        Vec3::new(8.0, 0.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        Rc::new(Masked::new(
+            steel,
+            Rc::new(ChainLink {
+                cells: 12.0,
+                wire: 0.04,
+            }),
+        )),
+    )));
+
+    let curtain: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.9)));
+    world.add(Box::new(Quad::new(
+        Point3::new(1.0, 0.0, -1.0),
+        Vec3::new(2.5, 0.0, -1.0),
+        Vec3::new(0.0, 3.0, 0.0),
+        Rc::new(Masked::new(curtain, Rc::new(SolidColor::scalar(0.4)))),
+    )));
+
+    for (center, color) in [
+        (Point3::new(-2.0, 0.8, -2.0), Color::new(0.8, 0.2, 0.1)),
+        (Point3::new(0.2, 0.8, -3.0), Color::new(0.1, 0.3, 0.8)),
+        (Point3::new(2.4, 0.8, -3.0), Color::new(0.9, 0.8, 0.2)),
+    ]
+    .iter()
+    {
+        world.add(Box::new(Sphere::new(
+            *center,
+            0.8,
+            Rc::new(Lambertian::new(*color)),
+        )));
+    }
+
+    // Leaves falling between the fence and the camera
+    let leaf = Rc::new(Leaf);
+    for _ in 0..40 {
+        let green = Color::new(
+            random_double_range(rng, 0.1, 0.4),
+            random_double_range(rng, 0.4, 0.7),
+            random_double_range(rng, 0.05, 0.2),
+        );
+        let corner = Point3::new(
+            random_double_range(rng, -4.0, 4.0),
+            random_double_range(rng, 0.2, 3.5),
+            random_double_range(rng, 1.5, 4.0),
+        );
+        let side = vec::unit_vector(Vec3::random_range(rng, -1.0, 1.0)) * 0.4;
+        let up = vec::unit_vector(vec::cross(&side, &Vec3::random_range(rng, -1.0, 1.0))) * 0.6;
+        world.add(Box::new(Quad::new(
+            corner,
+            side,
+            up,
+            Rc::new(Masked::new(Rc::new(Lambertian::new(green)), leaf.clone())),
+        )));
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 2.0, 9.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        9.0,
+    );
+
+    Scene::new(world, camera)
+}
+
+// Backlit leaves and a paper screen, thin sheets letting light through, next to a card printed
+// differently on each side
+pub fn sheets(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    // Low light behind the sheets
+    let light = Rc::new(DiffuseLight::new(Color::new(12.0, 11.0, 9.0)));
+    world.add(Box::new(Quad::new(
+        Point3::new(-3.0, 0.5, -5.0),
+        Vec3::new(6.0, 0.0, 0.0),
+        Vec3::new(0.0, 3.0, 0.0),
+        light,
+    )));
+
+    let paper = Rc::new(Translucent::new(
+        Color::new(0.6, 0.55, 0.5),
+        Color::new(0.3, 0.27, 0.2),
+    ));
+    world.add(Box::new(Quad::new(
+        Point3::new(-3.0, 0.0, -1.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 3.0, 0.0),
+        paper,
+    )));
+
+    let leaf: Rc<dyn Material> = Rc::new(Translucent::new(
+        Color::new(0.08, 0.25, 0.05),
+        Color::new(0.2, 0.5, 0.05),
+    ));
+    for i in 0..4 {
+        let angle = degrees_to_radians(i as f32 * 25.0 - 35.0);
+        world.add(Box::new(Quad::new(
+            Point3::new(-0.4 + i as f32 * 0.6, 0.6 + 0.4 * (i % 2) as f32, -1.0),
+            Vec3::new(0.5 * angle.cos(), 0.5 * angle.sin(), 0.2),
+            Vec3::new(-0.4 * angle.sin(), 0.8 * angle.cos(), 0.0),
+            Rc::new(Masked::new(leaf.clone(), Rc::new(Leaf))),
+        )));
+    }
+
+    // The same card seen from the back on the left and from the front on the right
+    let card = Rc::new(TwoSided::new(
+        Rc::new(Lambertian::new(Color::new(0.8, 0.1, 0.1))),
+        Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.8))),
+    ));
+    world.add(Box::new(Quad::new(
+        Point3::new(2.0, 0.0, 0.5),
+        Vec3::new(0.0, 0.0, -1.5),
+        Vec3::new(0.0, 2.0, 0.0),
+        card.clone(),
+    )));
+    world.add(Box::new(Quad::new(
+        Point3::new(3.0, 0.0, -1.0),
+        Vec3::new(0.0, 0.0, 1.5),
+        Vec3::new(0.0, 2.0, 0.0),
+        card,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 1.8, 6.0),
+        Point3::new(0.0, 1.2, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.02, 0.02, 0.03));
+    scene
+}
+
+// Plates from glossy to nearly mirror-like reflecting lights from small to large, all of the same
+// power. Light sampling works best for the large lights on the rough plates, scattering for the
+// small lights on the smooth plates.
+pub fn glossy_plates(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let floor = Rc::new(Lambertian::new(Color::new(0.3, 0.3, 0.3)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, -1.5, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        floor,
+    )));
+
+    let camera_position = Point3::new(0.0, 1.5, 4.0);
+    let light_position = Point3::new(0.0, 2.5, -4.0);
+
+    let plates = vec![
+        (0.3, Point3::new(0.0, 0.0, -0.2)),
+        (0.15, Point3::new(0.0, 0.2, -0.9)),
+        (0.06, Point3::new(0.0, 0.4, -1.6)),
+        (0.02, Point3::new(0.0, 0.6, -2.3)),
+    ];
+    for (roughness, center) in plates {
+        // Tilted to reflect the lights towards the camera
+        let normal = vec::unit_vector(
+            vec::unit_vector(camera_position - center) + vec::unit_vector(light_position - center),
+        );
+        let u = Vec3::new(4.0, 0.0, 0.0);
+        let v = vec::unit_vector(vec::cross(&normal, &u)) * 0.5;
+        world.add(Box::new(Quad::new(
+            center - u / 2.0 - v / 2.0,
+            u,
+            v,
+            Rc::new(Conductor::aluminium(roughness)),
+        )));
+    }
+
+    let radii = vec![0.03, 0.1, 0.3, 0.9];
+    for (i, radius) in radii.into_iter().enumerate() {
+        let radiance = 0.5 / (radius * radius);
+        world.add(Box::new(Sphere::new(
+            light_position + Vec3::new(-3.0 + 2.0 * i as f32, 0.0, 0.0),
+            radius,
+            Rc::new(DiffuseLight::new(Color::new(radiance, radiance, radiance))),
+        )));
+    }
+
+    let camera = Camera::new(
+        camera_position,
+        Point3::new(0.0, 0.3, -1.2),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        ascpect_ratio,
+        0.0,
+        5.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.01, 0.01, 0.01));
+    scene
+}
+
+// Objects lit by a low sun, a point light and two colored spot lights, none of them visible
+// geometry
+pub fn lamps(ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let white = Rc::new(Lambertian::new(Color::new(0.75, 0.75, 0.75)));
+    world.add(Box::new(BoxShape::new(
+        Point3::new(-2.6, 0.0, -1.6),
+        Point3::new(-1.6, 1.6, -0.6),
+        white.clone(),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, 0.7, -0.5),
+        0.7,
+        Rc::new(Conductor::copper(0.5)),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(2.0, 0.5, 0.0),
+        0.5,
+        white,
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 2.5, 7.0),
+        Point3::new(0.0, 0.6, -0.5),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.05, 0.07, 0.12));
+    scene.analytic_lights = vec![
+        Box::new(DirectionalLight::new(
+            Vec3::new(-1.0, 0.35, 0.4),
+            Color::new(2.0, 1.5, 1.0),
+            2.0,
+        )),
+        Box::new(
+            PointLight::new(Point3::new(1.0, 2.0, 1.5), Color::new(4.0, 3.5, 3.0)).with_range(6.0),
+        ),
+        Box::new(SpotLight::new(
+            Point3::new(-1.5, 4.0, 2.0),
+            Point3::new(0.0, 0.0, -0.5),
+            Color::new(40.0, 8.0, 8.0),
+            10.0,
+            18.0,
+        )),
+        Box::new(SpotLight::new(
+            Point3::new(3.0, 4.0, 1.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Color::new(8.0, 16.0, 40.0),
+            8.0,
+            14.0,
+        )),
+    ];
+    scene
+}
+
+// Street of a thousand small lamps of different brightness, sampled with the light tree
+pub fn many_lights(rng: &mut ThreadRng, ascpect_ratio: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let white = Rc::new(Lambertian::new(Color::new(0.7, 0.7, 0.7)));
+    for i in 0..6 {
+        world.add(Box::new(Sphere::new(
+            Point3::new(-2.5 + i as f32, 0.4, -2.0 - 2.0 * i as f32),
+            0.4,
+            white.clone(),
+        )));
+    }
+
+    // Lamps hanging at the same height, tilted in random directions
+    for i in 0..40 {
+        for j in 0..25 {
+            let center = Point3::new(
+                -8.0 + 0.4 * i as f32 + random_double_range(rng, -0.1, 0.1),
+                2.5 + random_double_range(rng, -0.5, 0.5),
+                2.0 - 0.6 * j as f32,
+            );
+            let u = vec::random_unit_vector(rng) * 0.1;
+            let v = vec::unit_vector(vec::cross(&u, &vec::random_unit_vector(rng))) * 0.1;
+            let brightness = 40.0 * random_double(rng).powi(4);
+            let color = Color::random_range(rng, 0.3, 1.0) * brightness;
+            world.add(Box::new(Triangle::new(
+                [center, center + u, center + v],
+                Rc::new(DiffuseLight::new(color)),
+            )));
+        }
+    }
+
+    let camera = Camera::new(
+        Point3::new(0.0, 1.5, 6.0),
+        Point3::new(0.0, 0.8, -4.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        50.0,
+        ascpect_ratio,
+        0.0,
+        10.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+    scene.light_selection = LightSelection::Tree;
+    scene
+}
+
+// Objects outdoors under a daylight sky with the sun at `elevation` degrees, lower for an evening
+// light, and the given turbidity for the haziness of the air
+pub fn daylight(ascpect_ratio: f32, elevation: f32, turbidity: f32) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Rc::new(Lambertian::new(Color::new(0.35, 0.33, 0.3)));
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground,
+    )));
+
+    let white = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    world.add(Box::new(BoxShape::new(
+        Point3::new(-3.0, 0.0, -3.0),
+        Point3::new(-1.8, 2.4, -1.8),
+        white.clone(),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(-0.6, 0.8, -0.6),
+        0.8,
+        white,
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(1.2, 0.6, 0.2),
+        0.6,
+        Rc::new(Dielectric::new(1.5)),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(2.6, 0.7, -1.4),
+        0.7,
+        Rc::new(Conductor::aluminium(0.2)),
+    )));
+
+    let camera = Camera::new(
+        Point3::new(0.0, 1.6, 7.0),
+        Point3::new(0.0, 1.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        45.0,
+        ascpect_ratio,
+        0.0,
+        7.0,
+    );
+
+    let mut scene = Scene::new(world, camera);
+    scene.set_sky(PhysicalSky::new(elevation, -60.0, turbidity));
+    scene
+}
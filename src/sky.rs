@@ -0,0 +1,220 @@
+// Analytic daylight sky of Preetham, Shirley and Smits 1999, "A Practical Analytic Model for
+// Daylight", and the sun it goes with. The y axis points up.
+use crate::light::DirectionalLight;
+use crate::utility::*;
+use crate::vec::{self, Color, Vec3};
+
+// Scale from luminances in kcd/m^2 to the radiance of the renderer, bringing the sunlight to a
+// few units
+const LUMINANCE_SCALE: f32 = 0.02;
+
+// Illuminance of the sun outside of the atmosphere, in klx
+const SOLAR_ILLUMINANCE: f32 = 128.0;
+
+// Angular diameter of the sun seen from the earth, in degrees
+const SUN_DIAMETER: f32 = 0.53;
+
+// Coefficients of the Perez distribution of one of the components of the sky color
+#[derive(Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    // Relative value at zenith angle `theta` and angle `gamma` from the sun
+    fn value(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+pub struct PhysicalSky {
+    // Unit direction towards the sun
+    sun_direction: Vec3,
+    turbidity: f32,
+    // Luminance and chromaticity at the zenith, over the Perez value of the zenith
+    zenith: [f32; 3],
+    perez: [Perez; 3],
+}
+
+impl PhysicalSky {
+    // Sun elevation above the horizon and azimuth from -z towards +x are in degrees. Turbidity
+    // goes from 2 for a very clear sky to about 10 for a hazy one.
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> PhysicalSky {
+        // The model doesn't hold for a sun under the horizon
+        let elevation = degrees_to_radians(elevation.clamp(0.5, 90.0));
+        let azimuth = degrees_to_radians(azimuth);
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+
+        let t = turbidity.clamp(1.7, 10.0);
+        let perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        let theta_s = PI / 2.0 - elevation;
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s * theta_s, theta_s, 1.0];
+            let ts = [t * t, t, 1.0];
+            (0..3)
+                .map(|i| ts[i] * (0..4).map(|j| m[i][j] * thetas[j]).sum::<f32>())
+                .sum::<f32>()
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let mut zenith = [luminance, x, y];
+        for (z, p) in zenith.iter_mut().zip(perez.iter()) {
+            *z /= p.value(1.0, theta_s);
+        }
+
+        PhysicalSky {
+            sun_direction,
+            turbidity: t,
+            zenith,
+            perez,
+        }
+    }
+
+    pub fn radiance(&self, direction: &Vec3) -> Color {
+        let mut direction = vec::unit_vector(*direction);
+        // Below the horizon the sky is continued by its color at the horizon, in the same
+        // azimuth. Straight down has none and takes that of +x.
+        if direction.y() < 0.0 {
+            let flat = Vec3::new(direction.x(), 0.0, direction.z());
+            direction = if flat.length_squared() > 0.0 {
+                vec::unit_vector(flat)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+        }
+        let cos_theta = direction.y().max(0.01);
+        let gamma = vec::dot(&direction, &self.sun_direction)
+            .clamp(-1.0, 1.0)
+            .acos();
+
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * self.perez[i].value(cos_theta, gamma));
+        xyy_to_rgb(x, y, luminance * LUMINANCE_SCALE)
+    }
+
+    // Sun matching the sky, reddened by the atmosphere it crosses
+    pub fn sun(&self) -> DirectionalLight {
+        let cos_theta = self.sun_direction.y();
+        let zenith_degrees = cos_theta.acos().to_degrees();
+        // Relative optical air mass of Kasten and Young 1989
+        let air_mass = 1.0 / (cos_theta + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+
+        // Rayleigh scattering and aerosol extinction with Angstrom's formula, at wavelengths in
+        // micrometers standing for the red, green and blue channels
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: f32| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-(rayleigh + aerosol) * air_mass).exp()
+        };
+        let irradiance = Color::new(
+            transmittance(0.65),
+            transmittance(0.57),
+            transmittance(0.475),
+        ) * (SOLAR_ILLUMINANCE * LUMINANCE_SCALE);
+        DirectionalLight::new(self.sun_direction, irradiance, SUN_DIAMETER)
+    }
+}
+
+// Linear sRGB color of CIE chromaticity `x`, `y` and luminance `luminance`
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::empty();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::Light;
+
+    fn assert_valid(color: &Color) {
+        for c in 0..3 {
+            assert!(color[c].is_finite() && color[c] >= 0.0, "{}", color);
+        }
+    }
+
+    #[test]
+    fn sky_is_brightest_around_the_sun() {
+        for (elevation, turbidity) in [(0.0, 2.0), (5.0, 3.0), (30.0, 2.5), (90.0, 10.0)].iter() {
+            let sky = PhysicalSky::new(*elevation, -60.0, *turbidity);
+            let around_sun = sky.radiance(&sky.sun_direction);
+            assert_valid(&around_sun);
+            assert!(around_sun.y() > 0.0);
+            let d = sky.sun_direction;
+            let away = sky.radiance(&Vec3::new(-d.x(), d.y(), -d.z()));
+            assert!(around_sun.y() >= away.y());
+
+            let sun = sky.sun();
+            assert!(sun.emitted(&sky.sun_direction).y() > 0.0);
+        }
+    }
+
+    #[test]
+    fn horizon_continues_below() {
+        let sky = PhysicalSky::new(20.0, 30.0, 3.0);
+        for azimuth in [0.0f32, 45.0, 160.0, 300.0].iter() {
+            let phi = degrees_to_radians(*azimuth);
+            let horizon = sky.radiance(&Vec3::new(phi.sin(), 0.0, -phi.cos()));
+            assert_valid(&horizon);
+            for depth in [0.01, 0.5, 5.0].iter() {
+                let below = sky.radiance(&Vec3::new(phi.sin(), -*depth, -phi.cos()));
+                for c in 0..3 {
+                    assert!((below[c] - horizon[c]).abs() <= 1e-5 * horizon[c].max(1.0));
+                }
+            }
+        }
+        assert_valid(&sky.radiance(&Vec3::new(0.0, -1.0, 0.0)));
+    }
+}
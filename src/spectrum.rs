@@ -0,0 +1,127 @@
+// Single-wavelength transport for dispersive materials. A path becomes monochromatic at its
+// first dispersive interaction; its throughput is then weighted by the color of the sampled
+// wavelength, which averages to white over the visible range.
+use crate::utility::*;
+use crate::vec::Color;
+use rand::prelude::*;
+
+// Visible range in nanometers
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 780.0;
+
+// Wavelength of the sodium d-line, at which indices of refraction are usually quoted
+const LAMBDA_D: f32 = 589.3;
+
+// Wavelength-dependent index of refraction, with wavelengths in micrometers in the formulas
+#[derive(Copy, Clone)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    // Schott N-BK7 crown glass
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    // Schott SF11 dense flint glass, strongly dispersive
+    pub fn sf11() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.737_597, 0.313_747_35, 1.898_781],
+            c: [0.013_188_707, 0.062_306_814, 155.236_3],
+        }
+    }
+
+    pub fn fused_silica() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+            c: [0.004_679_148, 0.013_512_06, 97.934],
+        }
+    }
+
+    pub fn diamond() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030_625, 0.011_236, 0.0],
+        }
+    }
+
+    // Index of refraction at `wavelength` in nanometers
+    pub fn index(&self, wavelength: f32) -> f32 {
+        let l = wavelength / 1000.0;
+        let l2 = l * l;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let mut n2 = 1.0;
+                for i in 0..3 {
+                    n2 += b[i] * l2 / (l2 - c[i]);
+                }
+                n2.sqrt()
+            }
+        }
+    }
+
+    // Index of refraction for paths that aren't monochromatic
+    pub fn nominal_index(&self) -> f32 {
+        self.index(LAMBDA_D)
+    }
+}
+
+pub fn sample_wavelength(rng: &mut ThreadRng) -> f32 {
+    random_double_range(rng, LAMBDA_MIN, LAMBDA_MAX)
+}
+
+// Weight of a path carrying only `wavelength` when sampled uniformly over the visible range
+pub fn wavelength_weight(wavelength: f32) -> Color {
+    let mean = MEAN_COLOR.with(|mean| *mean);
+    let rgb = wavelength_to_rgb(wavelength);
+    Color::new(rgb.x() / mean.x(), rgb.y() / mean.y(), rgb.z() / mean.z())
+}
+
+// Linear sRGB of a unit of light at `wavelength`, out of gamut parts clipped
+fn wavelength_to_rgb(wavelength: f32) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    Color::new(
+        (3.2406 * x - 1.5372 * y - 0.4986 * z).max(0.0),
+        (-0.9689 * x + 1.8758 * y + 0.0415 * z).max(0.0),
+        (0.0557 * x - 0.2040 * y + 1.0570 * z).max(0.0),
+    )
+}
+
+thread_local! {
+    // Computed once per thread, the first time a wavelength is weighted
+    static MEAN_COLOR: Color = mean_color();
+}
+
+// Average of `wavelength_to_rgb` over the visible range
+fn mean_color() -> Color {
+    let steps = 1000;
+    let mut sum = Color::empty();
+    for i in 0..steps {
+        let wavelength = LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * (i as f32 + 0.5) / steps as f32;
+        sum = sum + wavelength_to_rgb(wavelength);
+    }
+    sum / steps as f32
+}
+
+// CIE 1931 color matching functions, multi-lobe fit from Wyman et al. 2013, "Simple Analytic
+// Approximations to the CIE XYZ Color Matching Functions"
+fn cie_xyz(wavelength: f32) -> (f32, f32, f32) {
+    let g = |mu: f32, sigma1: f32, sigma2: f32| {
+        let sigma = if wavelength < mu { sigma1 } else { sigma2 };
+        let t = (wavelength - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    (x, y, z)
+}
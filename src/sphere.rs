@@ -1,4 +1,5 @@
-use crate::{aabb, hittable, material, ray, vec};
+use crate::{aabb, hittable, light_sampler, material, onb, ray, utility::*, vec};
+use rand::prelude::*;
 use std::rc::Rc;
 
 pub struct Sphere {
@@ -15,6 +16,18 @@ impl Sphere {
             material,
         }
     }
+
+    // One minus the cosine of the half-angle of the cone the sphere subtends from `origin`, none
+    // from inside. Computed from the squared sine so it doesn't round to zero for distant spheres.
+    fn cone_height(&self, origin: &vec::Point3) -> Option<f32> {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        let sin2_theta_max = radius_squared / distance_squared;
+        Some(sin2_theta_max / (1.0 + (1.0 - sin2_theta_max).sqrt()))
+    }
 }
 
 impl hittable::Hittable for Sphere {
@@ -40,6 +53,7 @@ impl hittable::Hittable for Sphere {
                 record.p = ray.at(record.t);
                 let outward_normal = (record.p - self.center) / self.radius;
                 record.set_normale_face(ray, &outward_normal);
+                set_sphere_uv(record, &outward_normal);
                 record.material = self.material.clone();
                 return true;
             }
@@ -50,6 +64,7 @@ impl hittable::Hittable for Sphere {
                 record.p = ray.at(record.t);
                 let outward_normal = (record.p - self.center) / self.radius;
                 record.set_normale_face(ray, &outward_normal);
+                set_sphere_uv(record, &outward_normal);
                 record.material = self.material.clone();
                 return true;
             }
@@ -57,11 +72,72 @@ impl hittable::Hittable for Sphere {
         false
     }
 
-    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
         *output_box = aabb::Aabb::new(
             self.center - vec::Vec3::new(self.radius, self.radius, self.radius),
             self.center + vec::Vec3::new(self.radius, self.radius, self.radius),
         );
         true
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Directions are sampled uniformly in the cone the sphere subtends from `origin`
+    fn pdf_value(&self, origin: &vec::Point3, direction: &vec::Vec3) -> f32 {
+        let mut record = hittable::HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if !self.hit(
+            &ray::Ray::new(*origin, *direction, 0.0),
+            &mut t_min,
+            &mut t_max,
+            &mut record,
+        ) {
+            return 0.0;
+        }
+
+        match self.cone_height(origin) {
+            Some(height) => 1.0 / (2.0 * PI * height),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &vec::Point3, rng: &mut ThreadRng) -> vec::Vec3 {
+        let height = match self.cone_height(origin) {
+            Some(height) => height,
+            None => return vec::random_unit_vector(rng),
+        };
+        let one_minus_cos = random_double(rng) * height;
+        let cos_theta = 1.0 - one_minus_cos;
+        let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double(rng);
+        let axis = onb::Onb::build_from_w(&(self.center - *origin));
+        axis.local(&vec::Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            cos_theta,
+        ))
+    }
+
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        let mut bounds = aabb::Aabb::empty();
+        self.bounding_box(0.0, 0.0, &mut bounds);
+        let radiance = self.material.emitted(0.5, 0.5, &self.center);
+        Some(light_sampler::LightBounds::sphere(
+            bounds,
+            self.radius,
+            &radiance,
+        ))
+    }
+}
+
+// Texture coordinates of a point given by its `outward_normal` on a unit sphere, u going around
+// the y axis from x = -1 and v from the bottom pole to the top one, with the tangent along u
+pub fn set_sphere_uv(record: &mut hittable::HitRecord, outward_normal: &vec::Vec3) {
+    let (x, y, z) = (outward_normal.x(), outward_normal.y(), outward_normal.z());
+    let phi = (-z).atan2(x) + PI;
+    record.u = phi / (2.0 * PI);
+    record.v = (-y).clamp(-1.0, 1.0).acos() / PI;
+    record.tangent = vec::Vec3::new(z, 0.0, -x);
 }
@@ -0,0 +1,212 @@
+use crate::vec::{Color, Point3};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+// Color varying over a surface, looked up by the texture coordinates and the hit point
+pub trait Texture {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color;
+
+    // Scalar parameters are read from the first channel
+    fn scalar(&self, u: f32, v: f32, p: &Point3) -> f32 {
+        self.value(u, v, p).x()
+    }
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+
+    // Constant scalar, for parameters such as roughness
+    pub fn scalar(value: f32) -> SolidColor {
+        SolidColor::new(Color::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+// Alternating 3D cells of two textures, `scale` cells per unit of distance
+pub struct Checker {
+    odd: Rc<dyn Texture>,
+    even: Rc<dyn Texture>,
+    scale: f32,
+}
+
+impl Checker {
+    pub fn new(odd: Rc<dyn Texture>, even: Rc<dyn Texture>, scale: f32) -> Checker {
+        Checker { odd, even, scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color {
+        let cell = (self.scale * p.x()).floor()
+            + (self.scale * p.y()).floor()
+            + (self.scale * p.z()).floor();
+        if cell as i64 % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Image wrapped over the texture coordinates, with u going right and v going up
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    // Pixels are given row by row from the top-left corner
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> ImageTexture {
+        assert!(
+            width > 0 && height > 0,
+            "ImageTexture needs at least one pixel"
+        );
+        assert!(
+            pixels.len() == width * height,
+            "ImageTexture data doesn't match its dimensions"
+        );
+        ImageTexture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Reads a binary (P6) or plain (P3) PPM file. Values are scaled to [0, 1] without decoding
+    // any gamma, so the file can hold data such as normals.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ImageTexture> {
+        ImageTexture::from_bytes(&fs::read(path)?)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<ImageTexture> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut position = 0;
+        let next_number = |position: &mut usize| -> io::Result<usize> {
+            ppm_field(bytes, position)
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| invalid("malformed PPM header"))
+        };
+
+        let magic = match ppm_field(bytes, &mut position) {
+            Some(m) if m == "P3" || m == "P6" => m,
+            _ => return Err(invalid("not a PPM file")),
+        };
+        let width = next_number(&mut position)?;
+        let height = next_number(&mut position)?;
+        let max_value = next_number(&mut position)?;
+        if width == 0 || height == 0 || max_value == 0 || max_value > 255 {
+            return Err(invalid("unsupported PPM dimensions or depth"));
+        }
+
+        // The dimensions can be anything in a corrupt file, so the size is computed without
+        // overflowing
+        let count = match width.checked_mul(height).and_then(|c| c.checked_mul(3)) {
+            Some(count) => count,
+            None => return Err(invalid("PPM dimensions are too large")),
+        };
+        let samples: Vec<usize> = if magic == "P3" {
+            (0..count)
+                .map(|_| next_number(&mut position))
+                .collect::<io::Result<_>>()?
+        } else {
+            // A single whitespace character separates the header from the raster
+            let start = position + 1;
+            let end = match start.checked_add(count) {
+                Some(end) if end <= bytes.len() => end,
+                _ => return Err(invalid("PPM raster is truncated")),
+            };
+            bytes[start..end].iter().map(|&b| b as usize).collect()
+        };
+
+        let scale = 1.0 / max_value as f32;
+        let pixels = samples
+            .chunks_exact(3)
+            .map(|c| Color::new(c[0] as f32, c[1] as f32, c[2] as f32) * scale)
+            .collect();
+        Ok(ImageTexture::new(width, height, pixels))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        let wrap = |x: f32| x - x.floor();
+        let i = ((wrap(u) * self.width as f32) as usize).min(self.width - 1);
+        let j = (((1.0 - wrap(v)) * self.height as f32) as usize).min(self.height - 1);
+        self.pixels[i + j * self.width]
+    }
+}
+
+// Next whitespace separated field of a PPM header, skipping comments from '#' to the end of a line
+fn ppm_field(bytes: &[u8], position: &mut usize) -> Option<String> {
+    loop {
+        while *position < bytes.len() && bytes[*position].is_ascii_whitespace() {
+            *position += 1;
+        }
+        if *position < bytes.len() && bytes[*position] == b'#' {
+            while *position < bytes.len() && bytes[*position] != b'\n' {
+                *position += 1;
+            }
+            continue;
+        }
+        break;
+    }
+
+    let start = *position;
+    while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() {
+        *position += 1;
+    }
+    if start == *position {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_binary_ppm() {
+        let mut bytes = b"P6\n# two pixels\n2 1\n255\n".to_vec();
+        bytes.extend_from_slice(&[255, 0, 0, 0, 0, 51]);
+        let texture = ImageTexture::from_bytes(&bytes).unwrap();
+        let p = Point3::empty();
+        assert_eq!(texture.value(0.25, 0.5, &p).x(), 1.0);
+        assert!((texture.value(0.75, 0.5, &p).z() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reject_truncated_raster() {
+        let bytes = b"P6 2 2 255\n\x00\x00\x00";
+        let error = ImageTexture::from_bytes(bytes).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_oversized_header() {
+        // Overflows the sample count on 64 bit targets, doesn't parse on 32 bit ones
+        let bytes = b"P6 4294967296 4294967296 255\n\x00\x00\x00";
+        let error = ImageTexture::from_bytes(bytes).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Overflows the end of the raster
+        let header = format!("P6 {} 1 255\n", usize::MAX / 3);
+        let error = ImageTexture::from_bytes(header.as_bytes()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
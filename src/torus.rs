@@ -0,0 +1,288 @@
+use crate::{aabb, disk, hittable, material, onb, ray, utility::*, vec, vec::*};
+use std::rc::Rc;
+
+// Torus around `center` lying in the plane perpendicular to `axis`. The tube of `minor_radius`
+// follows a circle of `major_radius`.
+pub struct Torus {
+    center: Point3,
+    axes: onb::Onb,
+    major_radius: f32,
+    minor_radius: f32,
+    material: Rc<dyn material::Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Rc<dyn material::Material>,
+    ) -> Torus {
+        Torus {
+            center,
+            axes: onb::Onb::build_from_w(&axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl hittable::Hittable for Torus {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        // Solve in the local frame with a unit direction, in double precision since the quartic
+        // coefficients lose precision quickly
+        let o = self.axes.to_local(&(ray.origin() - self.center));
+        let d = self.axes.to_local(&ray.direction());
+        let scale = d.length();
+        let d = d / scale;
+
+        let (ox, oy, oz) = (o.x() as f64, o.y() as f64, o.z() as f64);
+        let dz = d.z() as f64;
+        let r2 = (self.major_radius as f64).powi(2);
+        let f = (vec::dot(&o, &d)) as f64;
+        let oo = ox * ox + oy * oy + oz * oz;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with |p|^2 = s^2 + 2 f s + o.o
+        let e = oo + r2 - (self.minor_radius as f64).powi(2);
+        let coefficients = [
+            e * e - 4.0 * r2 * (oo - oz * oz),
+            4.0 * f * e - 8.0 * r2 * (f - oz * dz),
+            4.0 * f * f + 2.0 * e - 4.0 * r2 * (1.0 - dz * dz),
+            4.0 * f,
+            1.0,
+        ];
+
+        let mut closest = *t_max;
+        for s in solve_quartic(&coefficients).iter() {
+            let t = (*s as f32) / scale;
+            if t > *t_min && t < closest {
+                closest = t;
+            }
+        }
+        if closest >= *t_max {
+            return false;
+        }
+
+        let p = o + d * (closest * scale);
+        let ring = unit_vector(Vec3::new(p.x(), p.y(), 0.0)) * self.major_radius;
+        let local_normal = unit_vector(p - ring);
+        let tube = (p.x() * p.x() + p.y() * p.y()).sqrt() - self.major_radius;
+
+        record.t = closest;
+        record.p = ray.at(closest);
+        record.u = (p.y().atan2(p.x()) + PI) / (2.0 * PI);
+        record.v = (p.z().atan2(tube) + PI) / (2.0 * PI);
+        record.set_normale_face(ray, &self.axes.local(&local_normal));
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let w = self.axes.w();
+        let ring = disk::extent(&w, self.major_radius + self.minor_radius);
+        let tube = Vec3::new(w.x().abs(), w.y().abs(), w.z().abs()) * self.minor_radius;
+        let half = ring + tube;
+        *output_box = aabb::Aabb::new(self.center - half, self.center + half);
+        true
+    }
+}
+
+// Real roots of c[4] x^4 + c[3] x^3 + c[2] x^2 + c[1] x + c[0] using Ferrari's method,
+// polished with a few Newton iterations
+fn solve_quartic(c: &[f64; 5]) -> Vec<f64> {
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // Depressed quartic y^4 + p y^2 + q y + r with x = y - a / 4
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = cc - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * cc / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = vec![];
+    if q.abs() < 1e-12 {
+        // Biquadratic
+        for z in solve_quadratic(1.0, p, r).iter() {
+            if *z >= 0.0 {
+                roots.push(z.sqrt());
+                roots.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Largest root of the resolvent cubic is positive when q != 0
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return roots;
+        }
+        let sqrt_2m = (2.0 * m).sqrt();
+        let offset = q / (2.0 * sqrt_2m);
+        roots.extend(solve_quadratic(1.0, sqrt_2m, p / 2.0 + m - offset));
+        roots.extend(solve_quadratic(1.0, -sqrt_2m, p / 2.0 + m + offset));
+    }
+
+    roots
+        .iter()
+        .map(|y| {
+            let mut x = y - a / 4.0;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + cc) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + cc;
+                if df.abs() > 1e-12 {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    let root = discriminant.sqrt();
+    vec![(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+}
+
+// Largest real root of x^3 + a x^2 + b x + c
+fn solve_cubic(a: f64, b: f64, c: f64) -> f64 {
+    // Depressed cubic t^3 + p t + q with x = t - a / 3
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    let t = if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        (-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt()
+    } else {
+        // Three real roots, the k = 0 one of the trigonometric solution is the largest
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        m * theta.cos()
+    };
+    t - a / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+
+    fn torus() -> Torus {
+        Torus::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            2.0,
+            0.5,
+            Rc::new(material::Lambertian::new(Color::empty())),
+        )
+    }
+
+    // Every hit along the ray, in order
+    fn hits(torus: &Torus, origin: Point3, direction: Vec3) -> Vec<Point3> {
+        let ray = ray::Ray::new(origin, direction, 0.0);
+        let mut points = vec![];
+        let mut t_min = 0.001;
+        loop {
+            let mut record = hittable::HitRecord::empty();
+            let (mut t_start, mut t_max) = (t_min, INFINITY);
+            if !torus.hit(&ray, &mut t_start, &mut t_max, &mut record) {
+                return points;
+            }
+            points.push(record.p);
+            t_min = record.t + 0.001;
+        }
+    }
+
+    // Signed distance of `p` from the surface of the tube
+    fn tube_distance(p: &Point3) -> f32 {
+        let ring = (p.x() * p.x() + p.y() * p.y()).sqrt() - 2.0;
+        (ring * ring + p.z() * p.z()).sqrt() - 0.5
+    }
+
+    #[test]
+    fn ray_through_the_hole_misses() {
+        let torus = torus();
+        let points = hits(
+            &torus,
+            Point3::new(0.0, 0.0, -5.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert!(points.is_empty());
+        let points = hits(
+            &torus,
+            Point3::new(0.0, 3.0, -5.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert!(points.is_empty());
+    }
+
+    #[test]
+    fn axis_aligned_ray_hits_twice() {
+        let torus = torus();
+        let points = hits(
+            &torus,
+            Point3::new(2.0, 0.0, -5.0),
+            Vec3::new(0.0, 0.0, 2.0),
+        );
+        assert_eq!(points.len(), 2);
+        assert!((points[0].z() + 0.5).abs() < 1.0e-4);
+        assert!((points[1].z() - 0.5).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn ray_across_hits_four_times() {
+        let torus = torus();
+        let points = hits(
+            &torus,
+            Point3::new(-5.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert_eq!(points.len(), 4);
+        for (p, x) in points.iter().zip([-2.5, -1.5, 1.5, 2.5].iter()) {
+            assert!((p.x() - x).abs() < 1.0e-4);
+        }
+
+        // Oblique, with roots that aren't symmetric
+        let direction = Vec3::new(1.0, 0.3, 0.1);
+        let points = hits(&torus, Point3::new(-5.0, -0.9, -0.2), direction);
+        assert_eq!(points.len(), 4);
+        for p in points.iter() {
+            assert!(tube_distance(p).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn grazing_ray() {
+        let torus = torus();
+        // Just inside of the top of the tube, with two pairs of close roots
+        let points = hits(
+            &torus,
+            Point3::new(-5.0, 0.0, 0.49),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert_eq!(points.len(), 4);
+        for p in points.iter() {
+            assert!(tube_distance(p).abs() < 1.0e-3);
+        }
+
+        // Just above it
+        let points = hits(
+            &torus,
+            Point3::new(-5.0, 0.0, 0.51),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert!(points.is_empty());
+    }
+}
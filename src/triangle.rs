@@ -1,14 +1,49 @@
-use crate::{aabb, hittable, material, ray, vec, vec::*};
+use crate::{aabb, hittable, light_sampler, material, ray, utility::*, vec, vec::*};
+use rand::prelude::*;
 use std::rc::Rc;
 
 pub struct Triangle {
     vertices: [Point3; 3],
+    // Texture coordinates of the vertices
+    uvs: [(f32, f32); 3],
+    normal: Vec3,
+    tangent: Vec3,
+    area: f32,
     material: Rc<dyn material::Material>,
 }
 
 impl Triangle {
     pub fn new(vertices: [Point3; 3], material: Rc<dyn material::Material>) -> Triangle {
-        Triangle { vertices, material }
+        Triangle::with_uvs(vertices, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], material)
+    }
+
+    pub fn with_uvs(
+        vertices: [Point3; 3],
+        uvs: [(f32, f32); 3],
+        material: Rc<dyn material::Material>,
+    ) -> Triangle {
+        let e1 = vertices[1] - vertices[0];
+        let e2 = vertices[2] - vertices[0];
+
+        // Solve e1 = dp/du du1 + dp/dv dv1 and e2 = dp/du du2 + dp/dv dv2 for dp/du
+        let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+        let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+        let det = du1 * dv2 - du2 * dv1;
+        let tangent = if det.abs() > f32::EPSILON {
+            (e1 * dv2 - e2 * dv1) / det
+        } else {
+            e1
+        };
+
+        let n = vec::cross(&e1, &e2);
+        Triangle {
+            vertices,
+            uvs,
+            normal: vec::unit_vector(n),
+            area: 0.5 * n.length(),
+            tangent,
+            material,
+        }
     }
 }
 
@@ -21,49 +56,113 @@ impl hittable::Hittable for Triangle {
         t_max: &mut f32,
         record: &mut hittable::HitRecord,
     ) -> bool {
-        let e1 = self.vertices[0] - self.vertices[1];
-        let e2 = self.vertices[0] - self.vertices[2];
+        let e1 = self.vertices[1] - self.vertices[0];
+        let e2 = self.vertices[2] - self.vertices[0];
 
-        let normal = vec::cross(&e1, &e2);
-        // eprintln!("{}", normal);
-
-        // Calculating normal to surface
         let pvec = vec::cross(&ray.direction(), &e2);
         let det = vec::dot(&e1, &pvec);
 
         // If parallel to surface
-        if det < f32::EPSILON && det > -f32::EPSILON {
+        if det.abs() < f32::EPSILON {
             return false;
         }
 
+        // Barycentric coordinates of the hit point
         let inv_det = det.recip();
         let tvec = ray.origin() - self.vertices[0];
-        let u = vec::dot(&tvec, &pvec) * inv_det;
-        if u < 0.0 || u > 1.0 {
+        let b1 = vec::dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
             return false;
         }
 
         let qvec = vec::cross(&tvec, &e1);
-        let v = vec::dot(&ray.direction(), &qvec);
-        if v < 0.0 || u + v > 1.0 {
+        let b2 = vec::dot(&ray.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
             return false;
         }
 
         let t = vec::dot(&e2, &qvec) * inv_det;
-        if t < *t_max && t > *t_min {
-            record.t = vec::dot(&e2, &qvec) * inv_det;
-            record.p = ray.at(record.t);
-            // outward normal
-            // record.normal = normal;
-            // normale face
-            record.set_normale_face(ray, &normal);
-            record.material = self.material.clone();
-            return true;
+        if t >= *t_max || t <= *t_min {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        record.t = t;
+        record.p = ray.at(t);
+        record.u = b0 * self.uvs[0].0 + b1 * self.uvs[1].0 + b2 * self.uvs[2].0;
+        record.v = b0 * self.uvs[0].1 + b1 * self.uvs[1].1 + b2 * self.uvs[2].1;
+        record.set_normale_face(ray, &self.normal);
+        record.tangent = self.tangent;
+        record.material = self.material.clone();
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        let [a, b, c] = self.vertices;
+        let small = Point3::new(
+            a.x().min(b.x()).min(c.x()),
+            a.y().min(b.y()).min(c.y()),
+            a.z().min(b.z()).min(c.z()),
+        );
+        let big = Point3::new(
+            a.x().max(b.x()).max(c.x()),
+            a.y().max(b.y()).max(c.y()),
+            a.z().max(b.z()).max(c.z()),
+        );
+        // Padding keeps axis-aligned triangles from having a flat box
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        *output_box = aabb::Aabb::new(small - pad, big + pad);
+        true
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Points are sampled uniformly over the area, converted to solid angle at `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f32 {
+        let direction = vec::unit_vector(*direction);
+        let mut record = hittable::HitRecord::empty();
+        let (mut t_min, mut t_max) = (0.001, INFINITY);
+        if !self.hit(
+            &ray::Ray::new(*origin, direction, 0.0),
+            &mut t_min,
+            &mut t_max,
+            &mut record,
+        ) {
+            return 0.0;
+        }
+
+        let cosine = vec::dot(&direction, &self.normal).abs();
+        record.t * record.t / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut ThreadRng) -> Vec3 {
+        // Folding the unit square onto the triangle keeps the density uniform
+        let (mut b1, mut b2) = (random_double(rng), random_double(rng));
+        if b1 + b2 > 1.0 {
+            b1 = 1.0 - b1;
+            b2 = 1.0 - b2;
         }
-        false
+        let [v0, v1, v2] = self.vertices;
+        v0 + (v1 - v0) * b1 + (v2 - v0) * b2 - *origin
     }
 
-    fn bounding_box(&self, t0: f32, t1: f32, output_box: &mut aabb::Aabb) -> bool {
-        unimplemented!();
+    fn light_bounds(&self) -> Option<light_sampler::LightBounds> {
+        let mut bounds = aabb::Aabb::empty();
+        self.bounding_box(0.0, 0.0, &mut bounds);
+        let [a, b, c] = self.vertices;
+        let [(u0, v0), (u1, v1), (u2, v2)] = self.uvs;
+        let radiance = self.material.emitted(
+            (u0 + u1 + u2) / 3.0,
+            (v0 + v1 + v2) / 3.0,
+            &((a + b + c) / 3.0),
+        );
+        Some(light_sampler::LightBounds::flat(
+            bounds,
+            self.normal,
+            self.area,
+            &radiance,
+        ))
     }
 }
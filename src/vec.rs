@@ -190,3 +190,12 @@ pub fn random_in_unit_disc(rng: &mut ThreadRng) -> Vec3 {
         return p;
     }
 }
+
+// Direction on the hemisphere around +z with a density proportional to its cosine, z / pi
+pub fn random_cosine_direction(rng: &mut ThreadRng) -> Vec3 {
+    let r1 = random_double(rng);
+    let r2 = random_double(rng);
+    let phi = 2.0 * PI * r1;
+    let r = r2.sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt())
+}
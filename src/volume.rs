@@ -0,0 +1,355 @@
+use crate::{aabb, hittable, material, ray, utility::*, vec, vec::*};
+use rand::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+// Magic bytes at the start of a voxel grid file
+const MAGIC: &[u8; 4] = b"VOXG";
+// Density, albedo and emission of a voxel as little-endian f32 values
+const FLOATS_PER_VOXEL: usize = 7;
+
+// Properties of the volume at a point
+pub struct Voxel {
+    pub density: f32,
+    pub albedo: Color,
+    pub emission: Color,
+}
+
+// Dense grid of voxels with cell-centered samples
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    density: Vec<f32>,
+    albedo: Vec<Color>,
+    emission: Vec<Color>,
+}
+
+impl VoxelGrid {
+    // Voxels are indexed with x varying fastest, then y, then z
+    pub fn new(
+        dimensions: (usize, usize, usize),
+        density: Vec<f32>,
+        albedo: Vec<Color>,
+        emission: Vec<Color>,
+    ) -> VoxelGrid {
+        let (nx, ny, nz) = dimensions;
+        let count = nx * ny * nz;
+        assert!(count > 0, "VoxelGrid needs at least one voxel");
+        assert!(
+            density.len() == count && albedo.len() == count && emission.len() == count,
+            "VoxelGrid data doesn't match its dimensions"
+        );
+        VoxelGrid {
+            nx,
+            ny,
+            nz,
+            density,
+            albedo,
+            emission,
+        }
+    }
+
+    // Reads a raw grid file: the magic bytes "VOXG", the dimensions nx, ny and nz as
+    // little-endian u32, then for each voxel its density, albedo (r, g, b) and emission (r, g, b)
+    // as little-endian f32, with x varying fastest
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<VoxelGrid> {
+        VoxelGrid::from_bytes(&fs::read(path)?)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<VoxelGrid> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if bytes.len() < 16 || &bytes[0..4] != MAGIC {
+            return Err(invalid("not a voxel grid file"));
+        }
+        let read_u32 =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let (nx, ny, nz) = (
+            read_u32(4) as usize,
+            read_u32(8) as usize,
+            read_u32(12) as usize,
+        );
+
+        // The dimensions can be anything in a corrupt file, so the size is computed without
+        // overflowing
+        let count = nx
+            .checked_mul(ny)
+            .and_then(|c| c.checked_mul(nz))
+            .filter(|&c| c > 0);
+        let size = count
+            .and_then(|c| c.checked_mul(FLOATS_PER_VOXEL * 4))
+            .and_then(|s| s.checked_add(16));
+        let count = match (count, size) {
+            (Some(count), Some(size)) if size == bytes.len() => count,
+            _ => return Err(invalid("voxel grid size doesn't match its dimensions")),
+        };
+
+        let floats: Vec<f32> = bytes[16..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let mut density = Vec::with_capacity(count);
+        let mut albedo = Vec::with_capacity(count);
+        let mut emission = Vec::with_capacity(count);
+        for v in floats.chunks_exact(FLOATS_PER_VOXEL) {
+            density.push(v[0].max(0.0));
+            albedo.push(Color::new(v[1], v[2], v[3]));
+            emission.push(Color::new(v[4], v[5], v[6]));
+        }
+
+        Ok(VoxelGrid::new((nx, ny, nz), density, albedo, emission))
+    }
+
+    pub fn max_density(&self) -> f32 {
+        self.density.iter().cloned().fold(0.0, f32::max)
+    }
+
+    // Trilinear interpolation at `p` given in [0, 1]^3 grid coordinates
+    pub fn sample(&self, p: &Point3) -> Voxel {
+        let mut voxel = Voxel {
+            density: 0.0,
+            albedo: Color::empty(),
+            emission: Color::empty(),
+        };
+
+        let axis = |x: f32, n: usize| {
+            let g = x * n as f32 - 0.5;
+            let i0 = g.floor();
+            let f = g - i0;
+            let clamp_index = |i: f32| (i.max(0.0) as usize).min(n - 1);
+            (clamp_index(i0), clamp_index(i0 + 1.0), f)
+        };
+        let (x0, x1, fx) = axis(p.x(), self.nx);
+        let (y0, y1, fy) = axis(p.y(), self.ny);
+        let (z0, z1, fz) = axis(p.z(), self.nz);
+
+        for (z, wz) in [(z0, 1.0 - fz), (z1, fz)].iter() {
+            for (y, wy) in [(y0, 1.0 - fy), (y1, fy)].iter() {
+                for (x, wx) in [(x0, 1.0 - fx), (x1, fx)].iter() {
+                    let w = wx * wy * wz;
+                    let i = x + self.nx * (y + self.ny * z);
+                    voxel.density += self.density[i] * w;
+                    voxel.albedo = voxel.albedo + self.albedo[i] * w;
+                    voxel.emission = voxel.emission + self.emission[i] * w;
+                }
+            }
+        }
+        voxel
+    }
+}
+
+// Voxel grid stretched over a box in the scene
+struct PlacedGrid {
+    grid: VoxelGrid,
+    bounds: aabb::Aabb,
+}
+
+impl PlacedGrid {
+    fn voxel_at(&self, p: &Point3) -> Voxel {
+        let size = self.bounds.max() - self.bounds.min();
+        let local = *p - self.bounds.min();
+        self.grid.sample(&Point3::new(
+            local.x() / size.x(),
+            local.y() / size.y(),
+            local.z() / size.z(),
+        ))
+    }
+}
+
+// Isotropic phase function with the albedo and emission of the grid at the collision
+struct GridPhase {
+    volume: Rc<PlacedGrid>,
+}
+
+impl material::Material for GridPhase {
+    fn sample(
+        &self,
+        ray: &mut ray::Ray,
+        record: &hittable::HitRecord,
+        rng: &mut ThreadRng,
+    ) -> Option<material::BsdfSample> {
+        let wo = -vec::unit_vector(ray.direction());
+        let wi = vec::random_unit_vector(rng);
+        let lobe = material::Lobe::DIFFUSE | material::Lobe::TRANSMISSION;
+        material::BsdfSample::evaluated(self, record, &wo, wi, lobe)
+    }
+
+    // Collisions are absorptions with the probability of one minus the albedo, and only those
+    // emit, which keeps emission from being counted again where the volume scatters
+    fn emitted(&self, _u: f32, _v: f32, p: &Point3) -> Color {
+        let voxel = self.volume.voxel_at(p);
+        let absorbed = Color::new(1.0, 1.0, 1.0) - voxel.albedo;
+        voxel.emission
+            * Color::new(
+                absorbed.x().max(0.0),
+                absorbed.y().max(0.0),
+                absorbed.z().max(0.0),
+            )
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, record: &hittable::HitRecord, _wo: &Vec3, _wi: &Vec3) -> Color {
+        self.volume.voxel_at(&record.p).albedo / (4.0 * PI)
+    }
+
+    fn pdf(&self, _record: &hittable::HitRecord, _wo: &Vec3, _wi: &Vec3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// Volume with density varying according to a voxel grid stretched over `bounds`. Free paths are
+// sampled with delta tracking against the largest density of the grid.
+pub struct HeterogeneousMedium {
+    volume: Rc<PlacedGrid>,
+    density_scale: f32,
+    majorant: f32,
+    phase: Rc<dyn material::Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(grid: VoxelGrid, bounds: aabb::Aabb, density_scale: f32) -> HeterogeneousMedium {
+        let majorant = grid.max_density() * density_scale;
+        let volume = Rc::new(PlacedGrid { grid, bounds });
+        HeterogeneousMedium {
+            volume: volume.clone(),
+            density_scale,
+            majorant,
+            phase: Rc::new(GridPhase { volume }),
+        }
+    }
+
+    // Part of the ray between `t_min` and `t_max` that is inside the bounds
+    fn inside_interval(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let (mut t_enter, mut t_exit) = (t_min, t_max);
+        if self.majorant <= 0.0 || !self.volume.bounds.hit(ray, &mut t_enter, &mut t_exit) {
+            return None;
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+impl hittable::Hittable for HeterogeneousMedium {
+    fn hit(
+        &self,
+        ray: &ray::Ray,
+        t_min: &mut f32,
+        t_max: &mut f32,
+        record: &mut hittable::HitRecord,
+    ) -> bool {
+        let (t_enter, t_exit) = match self.inside_interval(ray, *t_min, *t_max) {
+            Some(interval) => interval,
+            None => return false,
+        };
+
+        // Delta tracking: tentative collisions are sampled against the majorant and accepted
+        // with the probability of the real density over the majorant
+        let mut rng = rand::thread_rng();
+        let step = 1.0 / (self.majorant * ray.direction().length());
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - random_double(&mut rng)).ln() * step;
+            if t >= t_exit {
+                return false;
+            }
+
+            let p = ray.at(t);
+            let density = self.volume.voxel_at(&p).density;
+            if random_double(&mut rng) * self.majorant < density * self.density_scale {
+                record.t = t;
+                record.p = p;
+                record.u = 0.0;
+                record.v = 0.0;
+                // Arbitrary, scattering in a volume does not depend on a surface orientation
+                record.normal = vec::Vec3::new(1.0, 0.0, 0.0);
+                record.front_face = true;
+                record.material = self.phase.clone();
+                return true;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32, output_box: &mut aabb::Aabb) -> bool {
+        *output_box = self.volume.bounds;
+        true
+    }
+
+    // Ratio tracking: the product of the null-collision probabilities at tentative collisions
+    fn transmittance(&self, ray: &ray::Ray, t_min: f32, t_max: f32) -> f32 {
+        let (t_enter, t_exit) = match self.inside_interval(ray, t_min, t_max) {
+            Some(interval) => interval,
+            None => return 1.0,
+        };
+
+        let mut rng = rand::thread_rng();
+        let step = 1.0 / (self.majorant * ray.direction().length());
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - random_double(&mut rng)).ln() * step;
+            if t >= t_exit {
+                return transmittance;
+            }
+
+            let density = self.volume.voxel_at(&ray.at(t)).density * self.density_scale;
+            transmittance *= 1.0 - density / self.majorant;
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(nx: u32, ny: u32, nz: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for n in [nx, ny, nz].iter() {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn load_single_voxel() {
+        let mut bytes = header(1, 1, 1);
+        for f in [0.5f32, 0.1, 0.2, 0.3, 1.0, 2.0, 3.0].iter() {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        let grid = VoxelGrid::from_bytes(&bytes).unwrap();
+        let voxel = grid.sample(&Point3::new(0.5, 0.5, 0.5));
+        assert_eq!(voxel.density, 0.5);
+        assert_eq!(voxel.emission.z(), 3.0);
+    }
+
+    #[test]
+    fn reject_truncated_data() {
+        let mut bytes = header(2, 2, 2);
+        bytes.extend_from_slice(&[0; 4 * 7 * 7]);
+        let error = VoxelGrid::from_bytes(&bytes).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = VoxelGrid::from_bytes(&bytes[..10]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_oversized_header() {
+        // Overflows the voxel count
+        let bytes = header(u32::MAX, u32::MAX, u32::MAX);
+        let error = VoxelGrid::from_bytes(&bytes).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Overflows the byte size on 64 bit targets, the voxel count on 32 bit ones
+        let bytes = header(u32::MAX, u32::MAX, 1);
+        let error = VoxelGrid::from_bytes(&bytes).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}